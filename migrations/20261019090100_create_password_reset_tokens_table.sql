@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE password_reset_tokens (
+    token_hash TEXT NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    PRIMARY KEY (token_hash)
+);
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Anonymous requests (e.g. subscriptions) are keyed by client instead of by user.
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_pkey;
+ALTER TABLE idempotency ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE idempotency ADD COLUMN client_id TEXT NULL;
+ALTER TABLE idempotency ADD CONSTRAINT idempotency_owner_check
+    CHECK ((user_id IS NULL) <> (client_id IS NULL));
+CREATE UNIQUE INDEX idempotency_user_id_key
+    ON idempotency (user_id, idempotency_key) WHERE user_id IS NOT NULL;
+CREATE UNIQUE INDEX idempotency_client_id_key
+    ON idempotency (client_id, idempotency_key) WHERE client_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Rows saved before fingerprinting was introduced have no fingerprint and are not checked.
+ALTER TABLE idempotency ADD COLUMN request_fingerprint TEXT NULL;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Lets the cleanup task find expired keys without scanning the whole table.
+CREATE INDEX idempotency_created_at ON idempotency (created_at);
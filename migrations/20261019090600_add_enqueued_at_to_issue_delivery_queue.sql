@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Lets us report how long deliveries have been waiting.
+ALTER TABLE issue_delivery_queue ADD COLUMN enqueued_at TIMESTAMPTZ NOT NULL DEFAULT now();
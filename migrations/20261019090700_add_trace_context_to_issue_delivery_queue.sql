@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The W3C trace context of the request that enqueued the delivery, serialized as JSON.
+ALTER TABLE issue_delivery_queue ADD COLUMN trace_context TEXT NULL;
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Background workers record when they were last alive, for readiness probes.
+CREATE TABLE worker_heartbeats (
+    worker_name TEXT NOT NULL PRIMARY KEY,
+    last_seen_at timestamptz NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Disabled users can neither log in nor use their API tokens.
+ALTER TABLE users ADD COLUMN disabled_at timestamptz NULL;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The language of the pages and emails we send to the subscriber.
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
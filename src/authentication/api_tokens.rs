@@ -0,0 +1,271 @@
+use actix_web::http::header::{HeaderMap, AUTHORIZATION};
+use actix_web::http::Method;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Makes leaked tokens easy to spot, e.g. by secret scanners.
+const API_TOKEN_PREFIX: &str = "rrat_";
+
+// What an API token is allowed to do. Cookie sessions can do everything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiTokenScope {
+    NewslettersPublish,
+    NewslettersRead,
+    SubscribersRead,
+}
+
+impl ApiTokenScope {
+    pub const ALL: [Self; 3] =
+        [Self::NewslettersPublish, Self::NewslettersRead, Self::SubscribersRead];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NewslettersPublish => "newsletters:publish",
+            Self::NewslettersRead => "newsletters:read",
+            Self::SubscribersRead => "subscribers:read",
+        }
+    }
+
+    // The scope a token needs to call the endpoint matching `route`, the pattern the
+    // route was registered with (e.g. `/api/v1/newsletters/{issue_id}`).
+    // Endpoints that are not listed here can only be used from a cookie session.
+    pub fn required_for(method: &Method, route: &str) -> Option<Self> {
+        match (method, route) {
+            (&Method::POST, "/admin/newsletters" | "/api/v1/newsletters") => {
+                Some(Self::NewslettersPublish)
+            }
+            (&Method::GET, "/api/v1/newsletters/{issue_id}") => Some(Self::NewslettersRead),
+            (&Method::GET, "/api/v1/subscribers") => Some(Self::SubscribersRead),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&str> for ApiTokenScope {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+            .ok_or_else(|| format!("{} is not a known API token scope.", value))
+    }
+}
+
+impl std::fmt::Display for ApiTokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiTokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+// The user and scopes an API token presented in a request resolves to.
+pub struct ApiTokenGrant {
+    pub user_id: Uuid,
+    pub scopes: Vec<ApiTokenScope>,
+}
+
+/// Extract the token from an `Authorization: Bearer` header, if the request has one.
+pub fn bearer_token(headers: &HeaderMap) -> Result<Option<Secret<String>>, anyhow::Error> {
+    let header = match headers.get(AUTHORIZATION) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    header
+        .to_str()
+        .ok()
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| Some(Secret::new(t.to_owned())))
+        .ok_or_else(|| anyhow::anyhow!("The authorization header is not a bearer token"))
+}
+
+/// Generate a random API token. It is only ever shown once, to the user creating it.
+pub fn generate_api_token() -> Secret<String> {
+    let mut rng = thread_rng();
+    let random: String =
+        std::iter::repeat_with(|| rng.sample(Alphanumeric)).map(char::from).take(40).collect();
+    Secret::new(format!("{}{}", API_TOKEN_PREFIX, random))
+}
+
+// Tokens are long and random: a fast digest is enough to keep a leaked table useless.
+fn hash_api_token(token: &Secret<String>) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+// Scopes are stored as text: unknown ones, e.g. from a newer release, are ignored.
+fn parse_scopes(scopes: Vec<String>) -> Vec<ApiTokenScope> {
+    scopes.iter().filter_map(|s| ApiTokenScope::try_from(s.as_str()).ok()).collect()
+}
+
+#[tracing::instrument(name = "Create API token", skip(token, pool))]
+pub async fn store_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiTokenScope],
+    expires_at: Option<DateTime<Utc>>,
+    token: &Secret<String>,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let token_id = Uuid::new_v4();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_owned()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (token_id, user_id, name, token_hash, scopes, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, now(), $6)
+        "#,
+        token_id,
+        user_id,
+        name,
+        hash_api_token(token),
+        &scopes,
+        expires_at,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the API token.")?;
+    Ok(token_id)
+}
+
+/// Resolve an API token presented by a client, recording that it has been used.
+///
+/// Returns `None` if the token is unknown, revoked or expired.
+#[tracing::instrument(name = "Authenticate API token", skip(token, pool))]
+pub async fn authenticate_api_token(
+    token: &Secret<String>,
+    pool: &PgPool,
+) -> Result<Option<ApiTokenGrant>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        WHERE
+            token_hash = $1 AND
+            (expires_at IS NULL OR expires_at > now()) AND
+            user_id IN (SELECT user_id FROM users WHERE disabled_at IS NULL)
+        RETURNING user_id, scopes
+        "#,
+        hash_api_token(token),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to authenticate an API token.")?;
+    Ok(row.map(|r| ApiTokenGrant { user_id: r.user_id, scopes: parse_scopes(r.scopes) }))
+}
+
+#[tracing::instrument(name = "List API tokens", skip(pool))]
+pub async fn get_api_tokens(user_id: Uuid, pool: &PgPool) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT token_id, name, scopes, created_at, expires_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve API tokens.")?;
+    Ok(rows
+        .into_iter()
+        .map(|r| ApiToken {
+            token_id: r.token_id,
+            name: r.name,
+            scopes: parse_scopes(r.scopes),
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+            last_used_at: r.last_used_at,
+        })
+        .collect())
+}
+
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    token_id: Uuid,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM api_tokens
+        WHERE user_id = $1 AND token_id = $2
+        "#,
+        user_id,
+        token_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the API token.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::Method;
+    use claim::assert_err;
+
+    use super::*;
+
+    #[test]
+    fn scopes_round_trip_through_their_string_form() {
+        for scope in ApiTokenScope::ALL {
+            assert_eq!(ApiTokenScope::try_from(scope.as_str()), Ok(scope));
+        }
+    }
+
+    #[test]
+    fn unknown_scopes_are_rejected() {
+        assert_err!(ApiTokenScope::try_from("admin:everything"));
+    }
+
+    #[test]
+    fn unknown_scopes_are_dropped_when_loading_a_token() {
+        let scopes = parse_scopes(vec!["subscribers:read".into(), "admin:everything".into()]);
+        assert_eq!(scopes, vec![ApiTokenScope::SubscribersRead]);
+    }
+
+    #[test]
+    fn publishing_a_newsletter_requires_the_publish_scope() {
+        assert_eq!(
+            ApiTokenScope::required_for(&Method::POST, "/admin/newsletters"),
+            Some(ApiTokenScope::NewslettersPublish)
+        );
+    }
+
+    #[test]
+    fn routes_are_matched_on_their_pattern() {
+        assert_eq!(
+            ApiTokenScope::required_for(&Method::GET, "/api/v1/newsletters/{issue_id}"),
+            Some(ApiTokenScope::NewslettersRead)
+        );
+    }
+
+    #[test]
+    fn session_only_endpoints_require_no_token_scope() {
+        assert_eq!(ApiTokenScope::required_for(&Method::POST, "/admin/password"), None);
+        assert_eq!(ApiTokenScope::required_for(&Method::GET, "/admin/newsletters"), None);
+    }
+
+    #[test]
+    fn generated_tokens_are_prefixed() {
+        let token = generate_api_token();
+        assert!(token.expose_secret().starts_with(API_TOKEN_PREFIX));
+        assert_eq!(token.expose_secret().len(), API_TOKEN_PREFIX.len() + 40);
+    }
+}
@@ -3,10 +3,17 @@ use std::ops::Deref;
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header::WWW_AUTHENTICATE;
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
 use actix_web_lab::middleware::Next;
+use secrecy::Secret;
+use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::{
+    authenticate_api_token, bearer_token, get_session_generation, ApiTokenScope, SessionIndex,
+    SessionMetadata,
+};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
@@ -19,6 +26,12 @@ impl std::fmt::Display for UserId {
     }
 }
 
+impl From<Uuid> for UserId {
+    fn from(user_id: Uuid) -> Self {
+        Self(user_id)
+    }
+}
+
 impl Deref for UserId {
     type Target = Uuid;
 
@@ -27,25 +40,106 @@ impl Deref for UserId {
     }
 }
 
-//
+// Lets through requests carrying either a valid session cookie or a valid
+// `Authorization: Bearer` API token, and records who made them as a `UserId`.
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = bearer_token(req.headers()).map_err(|e| {
+        bearer_error(HttpResponse::Unauthorized(), r#"Bearer error="invalid_request""#.into(), e)
+    })?;
+    if let Some(token) = token {
+        let user_id = authenticate_bearer_token(&req, token).await?;
+        req.extensions_mut().insert(UserId(user_id));
+        return next.call(req).await;
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
-        }
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
         None => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
-            Err(InternalError::from_response(e, response).into())
+            return Err(InternalError::from_response(e, response).into());
         }
+    };
+
+    // Sessions from an older generation were invalidated, e.g. by a password reset.
+    let pool = req.app_data::<web::Data<PgPool>>().ok_or_else(|| e500("Missing PgPool"))?;
+    let current_generation = get_session_generation(user_id, pool).await.map_err(e500)?;
+    if current_generation != Some(session.get_session_generation().map_err(e500)?) {
+        session.log_out();
+        let response = see_other("/login");
+        let e = anyhow::anyhow!("The session has been invalidated");
+        return Err(InternalError::from_response(e, response).into());
     }
+
+    // Sessions that are missing from the index were revoked, possibly from another device.
+    let session_index =
+        req.app_data::<web::Data<SessionIndex>>().ok_or_else(|| e500("Missing SessionIndex"))?;
+    match session.get_session_id().map_err(e500)? {
+        Some(session_id) => {
+            if !session_index.touch(user_id, session_id).await.map_err(e500)? {
+                session.log_out();
+                let response = see_other("/login");
+                let e = anyhow::anyhow!("The session has been revoked");
+                return Err(InternalError::from_response(e, response).into());
+            }
+        }
+        // Sessions opened before the index existed are registered on their next request.
+        None => {
+            let metadata = SessionMetadata::new(req.request());
+            session_index.register(user_id, &metadata).await.map_err(e500)?;
+            session.insert_session_id(metadata.session_id).map_err(e500)?;
+        }
+    }
+
+    req.extensions_mut().insert(UserId(user_id));
+    next.call(req).await
+}
+
+async fn authenticate_bearer_token(
+    req: &ServiceRequest,
+    token: Secret<String>,
+) -> Result<Uuid, actix_web::Error> {
+    let pool = req.app_data::<web::Data<PgPool>>().ok_or_else(|| e500("Missing PgPool"))?;
+    let grant = match authenticate_api_token(&token, pool).await.map_err(e500)? {
+        Some(grant) => grant,
+        None => {
+            return Err(bearer_error(
+                HttpResponse::Unauthorized(),
+                r#"Bearer error="invalid_token""#.into(),
+                anyhow::anyhow!("The API token is unknown, revoked or expired"),
+            ))
+        }
+    };
+
+    let route = req.match_pattern().unwrap_or_else(|| req.path().to_owned());
+    match ApiTokenScope::required_for(req.method(), &route) {
+        Some(scope) if grant.scopes.contains(&scope) => Ok(grant.user_id),
+        Some(scope) => Err(bearer_error(
+            HttpResponse::Forbidden(),
+            format!(r#"Bearer error="insufficient_scope", scope="{}""#, scope),
+            anyhow::anyhow!("The API token lacks the {} scope", scope),
+        )),
+        None => Err(bearer_error(
+            HttpResponse::Forbidden(),
+            r#"Bearer error="insufficient_scope""#.into(),
+            anyhow::anyhow!("The endpoint cannot be used with an API token"),
+        )),
+    }
+}
+
+fn bearer_error(
+    mut response: actix_web::HttpResponseBuilder,
+    challenge: String,
+    e: anyhow::Error,
+) -> actix_web::Error {
+    let response = response.insert_header((WWW_AUTHENTICATE, challenge)).finish();
+    InternalError::from_response(e, response).into()
 }
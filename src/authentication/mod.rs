@@ -1,5 +1,23 @@
+mod api_tokens;
 mod middleware;
 mod password;
+mod password_policy;
+mod password_reset;
+mod session_index;
+mod throttling;
+mod users;
 
+pub use api_tokens::{
+    authenticate_api_token, bearer_token, generate_api_token, get_api_tokens, revoke_api_token,
+    store_api_token, ApiToken, ApiTokenGrant, ApiTokenScope,
+};
 pub use middleware::{reject_anonymous_users, UserId};
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use password_policy::{PasswordPolicy, PasswordPolicyViolation};
+pub use password_reset::{
+    consume_password_reset_token, generate_password_reset_token, get_session_generation,
+    get_user_id_by_email, get_user_identity, invalidate_sessions, store_password_reset_token,
+};
+pub use session_index::{SessionIndex, SessionMetadata};
+pub use throttling::LoginThrottle;
+pub use users::{create_user, disable_user, enable_user, get_user_id_by_username, set_user_email};
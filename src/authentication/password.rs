@@ -1,9 +1,15 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use anyhow::Context;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use once_cell::sync::Lazy;
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 
+use crate::configuration::PasswordHashingSettings;
+use crate::metrics::PASSWORD_VERIFICATION_DURATION_SECONDS;
 use crate::telemetry::spawn_blocking_with_tracing;
 
 #[derive(thiserror::Error, Debug)]
@@ -29,7 +35,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND disabled_at IS NULL
         "#,
         username,
     )
@@ -40,35 +46,126 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+#[tracing::instrument(name = "Validate credentials", skip(credentials, hashing, pool))]
 pub async fn validate_credentials(
     credentials: Credentials,
+    hashing: &PasswordHashingSettings,
     pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
-    let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/\
-         iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
-
-    // conditionally assign stored_user_id and stored_password_hash
-    if let Some((stored_user_id, stored_password_hash)) =
-        get_stored_credentials(&credentials.username, pool).await?
-    {
-        user_id = Some(stored_user_id);
-        expected_password_hash = stored_password_hash;
-    }
+    let stored = get_stored_credentials(&credentials.username, pool).await?;
 
+    let expected_password_hash = stored.as_ref().map(|(_, hash)| hash.clone());
+    let password_candidate = credentials.password.clone();
+    let dummy_hashing = hashing.clone();
     spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+        // Unknown usernames go through the same verification as known ones, so that
+        // the response time does not tell them apart.
+        let expected_password_hash = match expected_password_hash {
+            Some(hash) => hash,
+            None => dummy_password_hash(&dummy_hashing)?,
+        };
+        verify_password_hash(expected_password_hash, password_candidate)
     })
     .await
     .context("Failed to spawn blocking task.")??;
 
-    user_id
+    let (user_id, stored_password_hash) = stored
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    // The password is only available in clear text right now: if the stored hash was
+    // computed with weaker parameters than the configured ones, upgrade it in the
+    // background rather than making the user wait for a second hash computation.
+    if needs_rehash(&stored_password_hash, hashing)? {
+        let hashing = hashing.clone();
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) = upgrade_password_hash(
+                user_id,
+                credentials.password,
+                stored_password_hash,
+                hashing,
+                &pool,
+            )
+            .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to upgrade a password hash"
+                );
+            }
+        });
+    }
+
+    Ok(user_id)
+}
+
+// The hash that the password of an unknown user is checked against. It is computed with
+// the configured parameters, like the hashes of actual users, once for each set of them.
+fn dummy_password_hash(hashing: &PasswordHashingSettings) -> Result<Secret<String>, anyhow::Error> {
+    // Memory cost, iterations and parallelism.
+    type Costs = (u32, u32, u32);
+    static DUMMY_HASHES: Lazy<Mutex<HashMap<Costs, Secret<String>>>> = Lazy::new(Default::default);
+
+    let key = (hashing.memory_cost_kib, hashing.iterations, hashing.parallelism);
+    if let Some(hash) = DUMMY_HASHES.lock().unwrap().get(&key) {
+        return Ok(hash.clone());
+    }
+    let hash = compute_password_hash(Secret::new(uuid::Uuid::new_v4().to_string()), hashing)?;
+    DUMMY_HASHES.lock().unwrap().insert(key, hash.clone());
+    Ok(hash)
+}
+
+// A stored hash needs to be recomputed if it is not an Argon2id v0x13 hash or if any of
+// its cost parameters is below the configured one.
+fn needs_rehash(
+    password_hash: &Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> Result<bool, anyhow::Error> {
+    let password_hash = PasswordHash::new(password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+    let stored = Params::try_from(&password_hash)
+        .context("Failed to read the parameters of the stored hash.")?;
+    let target = hashing.params().context("Invalid password hashing parameters.")?;
+    Ok(password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || stored.m_cost() < target.m_cost()
+        || stored.t_cost() < target.t_cost()
+        || stored.p_cost() < target.p_cost())
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, stored_password_hash, hashing, pool)
+)]
+async fn upgrade_password_hash(
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    stored_password_hash: Secret<String>,
+    hashing: PasswordHashingSettings,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    // The stored hash is part of the filter so that we never overwrite a password
+    // that was changed while we were hashing.
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        stored_password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the upgraded password hash in the database.")?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -79,6 +176,7 @@ fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<(), AuthError> {
+    let _timer = PASSWORD_VERIFICATION_DURATION_SECONDS.start_timer();
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
 
@@ -88,15 +186,20 @@ fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+// Runs in `transaction`, so that the new password only takes effect along with
+// whatever else has to change with it, e.g. invalidating the sessions of the user.
+#[tracing::instrument(name = "Change password", skip(password, hashing, transaction))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
-    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+    transaction: &mut Transaction<'_, Postgres>,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
     sqlx::query!(
         r#"
         UPDATE users
@@ -106,17 +209,41 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-    .execute(pool)
+    .execute(&mut *(*transaction))
     .await
     .context("Failed to change user's password in the database.")?;
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub(super) fn compute_password_hash(
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash =
-        Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(15000, 2, 1, None).unwrap())
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, hashing.params()?)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use argon2::password_hash::PasswordHash;
+    use argon2::Params;
+    use secrecy::ExposeSecret;
+
+    use super::dummy_password_hash;
+    use crate::configuration::PasswordHashingSettings;
+
+    #[test]
+    fn the_dummy_hash_uses_the_configured_parameters() {
+        let hashing =
+            PasswordHashingSettings { memory_cost_kib: 19456, iterations: 3, parallelism: 1 };
+
+        let hash = dummy_password_hash(&hashing).unwrap();
+
+        let params = Params::try_from(&PasswordHash::new(hash.expose_secret()).unwrap()).unwrap();
+        assert_eq!((params.m_cost(), params.t_cost(), params.p_cost()), (19456, 3, 1));
+        assert_eq!(dummy_password_hash(&hashing).unwrap().expose_secret(), hash.expose_secret());
+    }
+}
@@ -0,0 +1,161 @@
+use secrecy::{ExposeSecret, Secret};
+
+use crate::configuration::PasswordPolicySettings;
+
+// Argon2 happily hashes arbitrarily long inputs, which makes very long passwords a cheap
+// way to burn our CPU. Whatever the configuration says, we never accept more than this.
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+// A short list of the most commonly used passwords, one per line.
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PasswordPolicyViolation {
+    #[error("The new password must be at least {0} characters long.")]
+    TooShort(usize),
+    #[error("The new password must be at most {0} characters long.")]
+    TooLong(usize),
+    #[error("The new password must be different from the current password.")]
+    SameAsCurrent,
+    #[error("The new password is too common - please pick a less predictable one.")]
+    TooCommon,
+    #[error(
+        "The new password is too easy to guess - try a longer passphrase made of unrelated words."
+    )]
+    TooWeak,
+}
+
+#[derive(Clone)]
+pub struct PasswordPolicy(PasswordPolicySettings);
+
+impl From<PasswordPolicySettings> for PasswordPolicy {
+    fn from(settings: PasswordPolicySettings) -> Self {
+        Self(settings)
+    }
+}
+
+impl PasswordPolicy {
+    fn max_length(&self) -> usize {
+        self.0.max_length.min(MAX_PASSWORD_LENGTH)
+    }
+
+    // Checks a candidate password against the policy.
+    // `current_password` is the password being replaced, when the user supplied it,
+    // and `user_inputs` are user specific words (e.g. the username) that make a
+    // password easier to guess.
+    pub fn check(
+        &self,
+        candidate: &Secret<String>,
+        current_password: Option<&Secret<String>>,
+        user_inputs: &[&str],
+    ) -> Result<(), PasswordPolicyViolation> {
+        let candidate = candidate.expose_secret();
+        let length = candidate.chars().count();
+        if length < self.0.min_length {
+            return Err(PasswordPolicyViolation::TooShort(self.0.min_length));
+        }
+        if length > self.max_length() {
+            return Err(PasswordPolicyViolation::TooLong(self.max_length()));
+        }
+        if current_password.is_some_and(|current| current.expose_secret() == candidate) {
+            return Err(PasswordPolicyViolation::SameAsCurrent);
+        }
+        if is_common_password(candidate) {
+            return Err(PasswordPolicyViolation::TooCommon);
+        }
+        if let Some(min_strength_score) = self.0.min_strength_score {
+            // The candidate is not blank at this point, so estimating cannot fail.
+            let score = zxcvbn::zxcvbn(candidate, user_inputs).map(|e| e.score()).unwrap_or(0);
+            if score < min_strength_score {
+                return Err(PasswordPolicyViolation::TooWeak);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_common_password(candidate: &str) -> bool {
+    let candidate = candidate.to_lowercase();
+    COMMON_PASSWORDS.lines().any(|common| common == candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn policy(min_strength_score: Option<u8>) -> PasswordPolicy {
+        PasswordPolicySettings { min_length: 12, max_length: 128, min_strength_score }.into()
+    }
+
+    fn secret(s: &str) -> Secret<String> {
+        Secret::new(s.to_string())
+    }
+
+    #[test]
+    fn a_password_shorter_than_the_minimum_is_rejected() {
+        let outcome = policy(None).check(&secret("a".repeat(11).as_str()), None, &[]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::TooShort(12)));
+    }
+
+    #[test]
+    fn a_password_longer_than_the_maximum_is_rejected() {
+        let outcome = policy(None).check(&secret("a".repeat(129).as_str()), None, &[]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::TooLong(128)));
+    }
+
+    #[test]
+    fn the_maximum_length_is_capped_whatever_the_configuration() {
+        let policy: PasswordPolicy = PasswordPolicySettings {
+            min_length: 12,
+            max_length: usize::MAX,
+            min_strength_score: None,
+        }
+        .into();
+        let outcome =
+            policy.check(&secret("a".repeat(MAX_PASSWORD_LENGTH + 1).as_str()), None, &[]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::TooLong(MAX_PASSWORD_LENGTH)));
+    }
+
+    #[test]
+    fn length_is_counted_in_characters_not_bytes() {
+        assert_ok!(policy(None).check(&secret("ééééééééééé-ü"), None, &[]));
+    }
+
+    #[test]
+    fn the_current_password_is_rejected() {
+        let current = secret("my current passphrase");
+        let outcome = policy(None).check(&current, Some(&current), &[]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::SameAsCurrent));
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_regardless_of_case() {
+        let outcome = policy(None).check(&secret("Password1234"), None, &[]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::TooCommon));
+    }
+
+    #[test]
+    fn guessable_passwords_are_rejected_when_a_minimum_score_is_set() {
+        assert_err!(policy(Some(3)).check(&secret("aaaaaaaaaaaaaaaaaaaa"), None, &[]));
+        assert_ok!(policy(None).check(&secret("aaaaaaaaaaaaaaaaaaaa"), None, &[]));
+    }
+
+    #[test]
+    fn user_inputs_count_against_the_strength_score() {
+        let outcome =
+            policy(Some(3)).check(&secret("lawalutilitycoder"), None, &["lawalutilitycoder"]);
+        assert_eq!(outcome, Err(PasswordPolicyViolation::TooWeak));
+    }
+
+    #[test]
+    fn a_strong_password_is_accepted() {
+        assert_ok!(policy(Some(3)).check(
+            &secret("correct-stapler-orbit-violin"),
+            Some(&secret("everythinghastostartsomewhere")),
+            &["admin"],
+        ));
+    }
+}
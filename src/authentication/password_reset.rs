@@ -0,0 +1,168 @@
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// How long a password reset link stays valid after it has been emailed.
+const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+
+/// Generate a random 50 character-long case-sensitive password reset token.
+pub fn generate_password_reset_token() -> Secret<String> {
+    let mut rng = thread_rng();
+    Secret::new(
+        std::iter::repeat_with(|| rng.sample(Alphanumeric)).map(char::from).take(50).collect(),
+    )
+}
+
+// Only a SHA-256 digest of the token is persisted: a leaked table cannot be used to
+// reset anybody's password.
+fn hash_password_reset_token(token: &Secret<String>) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+#[tracing::instrument(name = "Get user id by email", skip(email, pool))]
+pub async fn get_user_id_by_email(
+    email: &str,
+    pool: &PgPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE email = $1 AND disabled_at IS NULL
+        "#,
+        email,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a user by email.")?;
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(name = "Store password reset token", skip(token, pool))]
+pub async fn store_password_reset_token(
+    user_id: Uuid,
+    token: &Secret<String>,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_password_reset_token(token),
+        user_id,
+        now,
+        now + Duration::minutes(RESET_TOKEN_TTL_MINUTES),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the password reset token.")?;
+    Ok(())
+}
+
+/// Redeem a password reset token, returning the id of the user it was issued to.
+///
+/// The token is deleted whether or not it has expired, together with any other
+/// outstanding token for the same user: a link can only ever be used once.
+#[tracing::instrument(name = "Consume password reset token", skip(token, transaction))]
+pub async fn consume_password_reset_token(
+    token: &Secret<String>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        DELETE FROM password_reset_tokens
+        WHERE token_hash = $1
+        RETURNING user_id, expires_at
+        "#,
+        hash_password_reset_token(token),
+    )
+    .fetch_optional(&mut *(*transaction))
+    .await
+    .context("Failed to delete the password reset token.")?;
+
+    let user_id = match row {
+        Some(r) if r.expires_at > Utc::now() => r.user_id,
+        _ => return Ok(None),
+    };
+
+    sqlx::query!(
+        r#"
+        DELETE FROM password_reset_tokens
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .execute(&mut *(*transaction))
+    .await
+    .context("Failed to delete outstanding password reset tokens.")?;
+    Ok(Some(user_id))
+}
+
+/// The username and email of the user, which their new password should not be built from.
+#[tracing::instrument(name = "Get user identity", skip(transaction))]
+pub async fn get_user_identity(
+    user_id: Uuid,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(String, Option<String>), anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(&mut *(*transaction))
+    .await
+    .context("Failed to perform a query to retrieve the username and email of a user.")?;
+    Ok((row.username, row.email))
+}
+
+/// Log the user out of every session they currently hold.
+///
+/// Sessions remember the generation they were created in; bumping it makes
+/// `reject_anonymous_users` turn all of them away.
+#[tracing::instrument(name = "Invalidate user sessions", skip(transaction))]
+pub async fn invalidate_sessions(
+    user_id: Uuid,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET session_generation = session_generation + 1
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .execute(&mut *(*transaction))
+    .await
+    .context("Failed to bump the session generation of the user.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get session generation", skip(pool))]
+pub async fn get_session_generation(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<i32>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT session_generation
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve the session generation.")?;
+    Ok(row.map(|r| r.session_generation))
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+use crate::utils::client_ip;
+
+// Matches the state TTL of the Redis session store: an entry outlives the session it
+// describes by at most a day of inactivity.
+const SESSION_TTL_SECONDS: i64 = 24 * 60 * 60;
+
+// Purpose: Keeps an index of the sessions each user holds, so that they can be listed
+// and revoked individually. `RedisSessionStore` keeps its keys to itself: every
+// session gets an id of our own, stored in `TypedSession` and checked by
+// `reject_anonymous_users`.
+#[derive(Clone)]
+pub struct SessionIndex {
+    connection: ConnectionManager,
+}
+
+pub struct SessionMetadata {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub user_agent: String,
+    pub ip_address: String,
+}
+
+impl SessionMetadata {
+    // Describes a brand new session opened by `request`.
+    pub fn new(request: &HttpRequest) -> Self {
+        let now = Utc::now();
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("unknown")
+            .to_owned();
+        Self {
+            session_id: Uuid::new_v4(),
+            created_at: now,
+            last_seen: now,
+            user_agent,
+            ip_address: client_ip(request),
+        }
+    }
+
+    fn from_fields(session_id: Uuid, mut fields: HashMap<String, String>) -> Option<Self> {
+        let timestamp = |name: &str| {
+            fields.get(name).and_then(|t| DateTime::parse_from_rfc3339(t).ok()).map(Into::into)
+        };
+        Some(Self {
+            session_id,
+            created_at: timestamp("created_at")?,
+            last_seen: timestamp("last_seen")?,
+            user_agent: fields.remove("user_agent")?,
+            ip_address: fields.remove("ip_address")?,
+        })
+    }
+}
+
+impl SessionIndex {
+    pub fn new(connection: ConnectionManager) -> Self {
+        Self { connection }
+    }
+
+    #[tracing::instrument(name = "Register session", skip(self, metadata))]
+    pub async fn register(
+        &self,
+        user_id: Uuid,
+        metadata: &SessionMetadata,
+    ) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_key = session_key(metadata.session_id);
+        redis::pipe()
+            .atomic()
+            .hset_multiple(
+                &session_key,
+                &[
+                    ("user_id", user_id.to_string()),
+                    ("created_at", metadata.created_at.to_rfc3339()),
+                    ("last_seen", metadata.last_seen.to_rfc3339()),
+                    ("user_agent", metadata.user_agent.clone()),
+                    ("ip_address", metadata.ip_address.clone()),
+                ],
+            )
+            .ignore()
+            .expire(&session_key, SESSION_TTL_SECONDS)
+            .ignore()
+            .sadd(user_sessions_key(user_id), metadata.session_id.to_string())
+            .ignore()
+            .expire(user_sessions_key(user_id), SESSION_TTL_SECONDS)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .context("Failed to register a session in the session index.")?;
+        Ok(())
+    }
+
+    // Records activity on a session.
+    // Returns `false` if the session is not (or no longer) in the index of the user.
+    #[tracing::instrument(name = "Touch session", skip(self))]
+    pub async fn touch(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_key = session_key(session_id);
+        let owner: Option<String> = connection
+            .hget(&session_key, "user_id")
+            .await
+            .context("Failed to read a session from the session index.")?;
+        if owner != Some(user_id.to_string()) {
+            return Ok(false);
+        }
+        redis::pipe()
+            .hset(&session_key, "last_seen", Utc::now().to_rfc3339())
+            .ignore()
+            .expire(&session_key, SESSION_TTL_SECONDS)
+            .ignore()
+            .expire(user_sessions_key(user_id), SESSION_TTL_SECONDS)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .context("Failed to refresh a session in the session index.")?;
+        Ok(true)
+    }
+
+    // Lists the live sessions of a user, most recently used first.
+    // Ids whose metadata has expired are dropped from the index along the way.
+    #[tracing::instrument(name = "List sessions", skip(self))]
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<SessionMetadata>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(user_sessions_key(user_id))
+            .await
+            .context("Failed to list the sessions of a user.")?;
+
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            let fields: HashMap<String, String> = connection
+                .hgetall(session_key(&session_id))
+                .await
+                .context("Failed to read a session from the session index.")?;
+            match Uuid::parse_str(&session_id)
+                .ok()
+                .and_then(|id| SessionMetadata::from_fields(id, fields))
+            {
+                Some(metadata) => sessions.push(metadata),
+                None => connection
+                    .srem::<_, _, ()>(user_sessions_key(user_id), &session_id)
+                    .await
+                    .context("Failed to prune an expired session from the index.")?,
+            }
+        }
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen));
+        Ok(sessions)
+    }
+
+    // Revokes a session of the user. Ids of sessions owned by somebody else are ignored.
+    #[tracing::instrument(name = "Revoke session", skip(self))]
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let owner: Option<String> = connection
+            .hget(session_key(session_id), "user_id")
+            .await
+            .context("Failed to read a session from the session index.")?;
+        if owner != Some(user_id.to_string()) {
+            return Ok(());
+        }
+        redis::pipe()
+            .atomic()
+            .del(session_key(session_id))
+            .ignore()
+            .srem(user_sessions_key(user_id), session_id.to_string())
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .context("Failed to revoke a session.")?;
+        Ok(())
+    }
+
+    // Revokes every session of the user, except `keep` if provided.
+    #[tracing::instrument(name = "Revoke all sessions", skip(self))]
+    pub async fn revoke_all(&self, user_id: Uuid, keep: Option<Uuid>) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(user_sessions_key(user_id))
+            .await
+            .context("Failed to list the sessions of a user.")?;
+        let keep = keep.map(|id| id.to_string());
+        for session_id in session_ids.iter().filter(|id| Some(*id) != keep.as_ref()) {
+            redis::pipe()
+                .atomic()
+                .del(session_key(session_id))
+                .ignore()
+                .srem(user_sessions_key(user_id), session_id)
+                .ignore()
+                .query_async::<_, ()>(&mut connection)
+                .await
+                .context("Failed to revoke a session.")?;
+        }
+        Ok(())
+    }
+}
+
+fn user_sessions_key(user_id: Uuid) -> String {
+    format!("user_sessions:{}", user_id)
+}
+
+fn session_key(session_id: impl std::fmt::Display) -> String {
+    format!("user_session:{}", session_id)
+}
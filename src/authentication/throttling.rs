@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use crate::configuration::LoginThrottlingSettings;
+
+// Purpose: Keeps track of failed login attempts in Redis, so that credentials
+// cannot be guessed by hammering `/login`.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    settings: LoginThrottlingSettings,
+}
+
+impl LoginThrottle {
+    pub fn new(connection: ConnectionManager, settings: LoginThrottlingSettings) -> Self {
+        Self { connection, settings }
+    }
+
+    fn counters(&self, username: &str, client_ip: &str) -> [(String, u64); 2] {
+        [
+            (username_key(username), self.settings.max_failures_per_username),
+            (format!("login_failures:ip:{}", client_ip), self.settings.max_failures_per_ip),
+        ]
+    }
+
+    // Returns how long the caller has to wait before trying again, if either the
+    // username or the client IP is currently locked out.
+    #[tracing::instrument(name = "Check login lockout", skip(self))]
+    pub async fn lockout_remaining(
+        &self,
+        username: &str,
+        client_ip: &str,
+    ) -> Result<Option<Duration>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let mut remaining = None;
+        for (key, max_failures) in self.counters(username, client_ip) {
+            let failures: Option<u64> =
+                connection.get(&key).await.context("Failed to read a login failure counter.")?;
+            if failures.unwrap_or(0) >= max_failures {
+                let ttl: i64 =
+                    connection.ttl(&key).await.context("Failed to read a lockout expiry.")?;
+                let ttl = Duration::from_secs(ttl.max(1) as u64);
+                remaining = remaining.max(Some(ttl));
+            }
+        }
+        Ok(remaining)
+    }
+
+    // Counts a failed attempt against both the username and the client IP.
+    // Every failure pushes the expiry of the counters back, so a lockout only ends
+    // after a full `lockout_seconds` without attempts.
+    #[tracing::instrument(name = "Register failed login", skip(self))]
+    pub async fn register_failure(
+        &self,
+        username: &str,
+        client_ip: &str,
+    ) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        for (key, max_failures) in self.counters(username, client_ip) {
+            let (failures,): (u64,) = redis::pipe()
+                .atomic()
+                .incr(&key, 1)
+                .expire(&key, self.settings.lockout_seconds as i64)
+                .ignore()
+                .query_async(&mut connection)
+                .await
+                .context("Failed to increment a login failure counter.")?;
+            if failures == max_failures {
+                tracing::warn!(
+                    username,
+                    client_ip,
+                    lockout_key = %key,
+                    lockout_seconds = self.settings.lockout_seconds,
+                    "Too many failed login attempts - locking out further attempts"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Clears the failures recorded against a username after a successful login.
+    // The per-IP counter is left alone: an attacker must not be able to reset it by
+    // logging into their own account.
+    #[tracing::instrument(name = "Reset failed logins", skip(self))]
+    pub async fn reset(&self, username: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(username_key(username))
+            .await
+            .context("Failed to reset a login failure counter.")?;
+        Ok(())
+    }
+}
+
+fn username_key(username: &str) -> String {
+    format!("login_failures:username:{}", username)
+}
@@ -0,0 +1,102 @@
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::password::compute_password_hash;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::SubscriberEmail;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+#[tracing::instrument(name = "Create user", skip(password, hashing, pool))]
+pub async fn create_user(
+    username: &str,
+    email: Option<&SubscriberEmail>,
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        username,
+        email.map(AsRef::as_ref),
+        password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the new user.")?;
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Get user id by username", skip(pool))]
+pub async fn get_user_id_by_username(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!("SELECT user_id FROM users WHERE username = $1", username)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to retrieve a user by username.")?;
+    Ok(row.map(|r| r.user_id))
+}
+
+/// Set the address password reset links are sent to.
+///
+/// Returns `false` if there is no such user.
+#[tracing::instrument(name = "Set user email", skip(pool))]
+pub async fn set_user_email(
+    username: &str,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let outcome =
+        sqlx::query!("UPDATE users SET email = $1 WHERE username = $2", email.as_ref(), username,)
+            .execute(pool)
+            .await
+            .context("Failed to set the email of the user.")?;
+    Ok(outcome.rows_affected() > 0)
+}
+
+/// Disable a user, logging them out of every session.
+///
+/// Returns `false` if there is no such user.
+#[tracing::instrument(name = "Disable user", skip(pool))]
+pub async fn disable_user(username: &str, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE users
+        SET
+            disabled_at = COALESCE(disabled_at, now()),
+            session_generation = session_generation + 1
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to disable the user.")?;
+    Ok(outcome.rows_affected() > 0)
+}
+
+/// Let a disabled user log in again.
+///
+/// Returns `false` if there is no such user.
+#[tracing::instrument(name = "Enable user", skip(pool))]
+pub async fn enable_user(username: &str, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let outcome =
+        sqlx::query!("UPDATE users SET disabled_at = NULL WHERE username = $1", username,)
+            .execute(pool)
+            .await
+            .context("Failed to enable the user.")?;
+    Ok(outcome.rows_affected() > 0)
+}
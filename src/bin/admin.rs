@@ -0,0 +1,198 @@
+//! Operational tasks that have no place in the web interface.
+//!
+//! Reads the same configuration as the application. Passwords are read from the first
+//! line of stdin, so that they do not end up in the shell history.
+use std::io::BufRead;
+
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use robust_rust::authentication::{
+    change_password, create_user, disable_user, enable_user, get_user_id_by_username,
+    invalidate_sessions, set_user_email, PasswordPolicy,
+};
+use robust_rust::configuration::{get_configuration, Settings};
+use robust_rust::domain::SubscriberEmail;
+use robust_rust::idempotency::delete_expired_idempotency_keys;
+use robust_rust::issue_delivery_worker::{list_queued_deliveries, requeue_issue};
+use robust_rust::startup::get_connection_pool;
+use robust_rust::telemetry::{get_subscriber, init_subscriber};
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(name = "robust-rust-admin", about = "Administer a robust-rust deployment")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the users that can log into the admin dashboard.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Inspect and refill the issue delivery queue.
+    #[command(subcommand)]
+    Queue(QueueCommand),
+    /// Maintain the stored idempotency keys.
+    #[command(subcommand)]
+    Idempotency(IdempotencyCommand),
+}
+
+#[derive(Subcommand)]
+enum UsersCommand {
+    /// Create a user. The password is read from stdin.
+    Create {
+        username: String,
+        /// Where password reset links are sent.
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Set the address password reset links are sent to.
+    SetEmail { username: String, email: String },
+    /// Stop a user from logging in and end all of their sessions.
+    Disable { username: String },
+    /// Let a disabled user log in again.
+    Enable { username: String },
+    /// Set a new password, read from stdin, and end all of the user's sessions.
+    ResetPassword { username: String },
+}
+
+#[derive(Subcommand)]
+enum QueueCommand {
+    /// List the deliveries waiting in the queue.
+    List,
+    /// Queue an issue again for every confirmed subscriber.
+    Requeue {
+        issue_id: Uuid,
+        /// Only queue the issue for this subscriber.
+        #[arg(long)]
+        email: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IdempotencyCommand {
+    /// Delete the idempotency keys that have outlived their TTL.
+    Purge,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let configuration = get_configuration().context("Failed to read configuration.")?;
+    // Keep stdout for the output of the commands.
+    let subscriber =
+        get_subscriber("robust_rust_admin".into(), "warn".into(), std::io::stderr, None)?;
+    init_subscriber(subscriber);
+    let pool = get_connection_pool(&configuration.database);
+
+    match cli.command {
+        Command::Users(command) => run_users_command(command, &configuration, &pool).await,
+        Command::Queue(command) => run_queue_command(command, &pool).await,
+        Command::Idempotency(IdempotencyCommand::Purge) => {
+            let n_purged = delete_expired_idempotency_keys(&pool, &configuration.idempotency)
+                .await
+                .context("Failed to purge expired idempotency keys.")?;
+            println!("Purged {} expired idempotency keys.", n_purged);
+            Ok(())
+        }
+    }
+}
+
+async fn run_users_command(
+    command: UsersCommand,
+    configuration: &Settings,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    match command {
+        UsersCommand::Create { username, email } => {
+            let email = email.map(parse_email).transpose()?;
+            let password = read_password(configuration, &username)?;
+            let user_id = create_user(
+                &username,
+                email.as_ref(),
+                password,
+                &configuration.password_hashing,
+                pool,
+            )
+            .await?;
+            println!("Created user {} with id {}.", username, user_id);
+        }
+        UsersCommand::SetEmail { username, email } => {
+            let email = parse_email(email)?;
+            if !set_user_email(&username, &email, pool).await? {
+                return Err(no_such_user(&username));
+            }
+            println!("Set the email of user {} to {}.", username, email);
+        }
+        UsersCommand::Disable { username } => {
+            if !disable_user(&username, pool).await? {
+                return Err(no_such_user(&username));
+            }
+            println!("Disabled user {}.", username);
+        }
+        UsersCommand::Enable { username } => {
+            if !enable_user(&username, pool).await? {
+                return Err(no_such_user(&username));
+            }
+            println!("Enabled user {}.", username);
+        }
+        UsersCommand::ResetPassword { username } => {
+            let user_id = get_user_id_by_username(&username, pool)
+                .await?
+                .ok_or_else(|| no_such_user(&username))?;
+            let password = read_password(configuration, &username)?;
+            let mut transaction = pool.begin().await.context("Failed to begin transaction.")?;
+            change_password(user_id, password, &configuration.password_hashing, &mut transaction)
+                .await?;
+            invalidate_sessions(user_id, &mut transaction).await?;
+            transaction.commit().await.context("Failed to commit SQL transaction.")?;
+            println!("Reset the password of user {}.", username);
+        }
+    }
+    Ok(())
+}
+
+async fn run_queue_command(command: QueueCommand, pool: &PgPool) -> anyhow::Result<()> {
+    match command {
+        QueueCommand::List => {
+            for delivery in list_queued_deliveries(pool).await? {
+                println!(
+                    "{}\t{}\t{}",
+                    delivery.issue_id,
+                    delivery.email,
+                    delivery.enqueued_at.to_rfc3339()
+                );
+            }
+        }
+        QueueCommand::Requeue { issue_id, email } => {
+            let n_queued = requeue_issue(pool, issue_id, email.as_deref()).await?;
+            println!("Queued {} deliveries of issue {}.", n_queued, issue_id);
+        }
+    }
+    Ok(())
+}
+
+// Reads the password from the first line of stdin and checks it against the policy
+// the web interface enforces.
+fn read_password(configuration: &Settings, username: &str) -> anyhow::Result<Secret<String>> {
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line).context("Failed to read the password.")?;
+    let password = Secret::new(line.trim_end_matches(['\r', '\n']).to_string());
+    PasswordPolicy::from(configuration.password_policy.clone()).check(
+        &password,
+        None,
+        &[username],
+    )?;
+    Ok(password)
+}
+
+fn parse_email(email: String) -> anyhow::Result<SubscriberEmail> {
+    SubscriberEmail::parse(email).map_err(|e| anyhow!("{}", e))
+}
+
+fn no_such_user(username: &str) -> anyhow::Error {
+    anyhow!("There is no user named {}.", username)
+}
@@ -1,6 +1,10 @@
 use std::convert::{TryFrom, TryInto};
+use std::net::IpAddr;
 
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header::HeaderValue;
 use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
@@ -15,6 +19,15 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub login_throttling: LoginThrottlingSettings,
+    pub subscription_throttling: SubscriptionThrottlingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub idempotency: IdempotencySettings,
+    pub security_headers: SecurityHeadersSettings,
+    pub cookies: CookieSettings,
+    pub metrics: MetricsSettings,
+    pub otlp: Option<OtlpSettings>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -23,7 +36,53 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
+    // Cookies are signed with `hmac_secret`. Cookies signed with one of the
+    // `previous_hmac_secrets` are still accepted, so that the secret can be rotated
+    // without logging everybody out.
     pub hmac_secret: Secret<String>,
+    #[serde(default, deserialize_with = "deserialize_secret_list")]
+    pub previous_hmac_secrets: Vec<Secret<String>>,
+    // The reverse proxies in front of us. The client address is only taken from
+    // `X-Forwarded-For` when the request comes from one of them: anybody else could
+    // put whatever they like in it.
+    #[serde(default, deserialize_with = "deserialize_ip_list")]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+// Accepts a list, or a whitespace separated string - which is what an environment
+// variable or a secret file holds.
+fn deserialize_string_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum StringList {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    Ok(match StringList::deserialize(deserializer)? {
+        StringList::List(items) => items,
+        StringList::Joined(items) => items.split_whitespace().map(String::from).collect(),
+    })
+}
+
+fn deserialize_secret_list<'de, D>(deserializer: D) -> Result<Vec<Secret<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(deserialize_string_list(deserializer)?.into_iter().map(Secret::new).collect())
+}
+
+fn deserialize_ip_list<'de, D>(deserializer: D) -> Result<Vec<IpAddr>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_string_list(deserializer)?
+        .iter()
+        .map(|ip| ip.parse().map_err(serde::de::Error::custom))
+        .collect()
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -36,13 +95,14 @@ pub struct EmailClientSettings {
 
 impl EmailClientSettings {
     pub fn client(self) -> EmailClient {
+        // `Settings::validate` has checked the sender already.
         let sender_email = self.sender().expect("Invalid sender email address");
         let timeout = std::time::Duration::from_millis(self.timeout_ms);
         EmailClient::new(self.base_url, sender_email, self.authorization_token, timeout)
     }
 
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
-        SubscriberEmail::parse(self.sender_email.clone())
+        SubscriberEmail::parse(self.sender_email.clone()).map_err(|e| e.to_string())
     }
 
     pub fn timeout(&self) -> std::time::Duration {
@@ -50,6 +110,91 @@ impl EmailClientSettings {
     }
 }
 
+// Failed login attempts are counted per username and per client IP; once either
+// counter reaches its threshold, further attempts are refused until it expires.
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginThrottlingSettings {
+    pub max_failures_per_username: u64,
+    pub max_failures_per_ip: u64,
+    pub lockout_seconds: u64,
+}
+
+// Subscription requests are counted per client IP and per recipient over fixed windows.
+// A recipient is sent at most one confirmation email every
+// `confirmation_email_cooldown_seconds`; 0 turns the cooldown off.
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionThrottlingSettings {
+    pub max_requests_per_ip: u64,
+    pub ip_window_seconds: u64,
+    pub max_requests_per_recipient: u64,
+    pub recipient_window_seconds: u64,
+    pub confirmation_email_cooldown_seconds: u64,
+}
+
+// Rules a new password has to follow. `min_strength_score` is a zxcvbn score
+// between 0 and 4; leave it out to skip the strength estimation.
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordPolicySettings {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub min_strength_score: Option<u8>,
+}
+
+// Argon2id cost parameters for new password hashes. Stored hashes computed with
+// weaker parameters are upgraded the next time their owner logs in.
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashingSettings {
+    pub memory_cost_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(self.memory_cost_kib, self.iterations, self.parallelism, None)
+    }
+}
+
+// A request reusing the idempotency key of a request still in flight waits up to
+// `in_flight_timeout_ms` for its response before being told to retry later.
+// Keys expire `ttl_hours` after their first use; expired keys are purged every
+// `cleanup_interval_seconds`, `cleanup_batch_size` rows at a time.
+#[derive(serde::Deserialize, Clone)]
+pub struct IdempotencySettings {
+    pub in_flight_timeout_ms: u64,
+    pub ttl_hours: u64,
+    pub cleanup_interval_seconds: u64,
+    pub cleanup_batch_size: i64,
+}
+
+// The headers hardening our pages against framing, content injection and leaky
+// referrers. `content_security_policy` can refer to the nonce of the request as
+// `{nonce}`; with `csp_report_only` violations are only reported to `/csp-report`
+// rather than blocked. An `hsts_max_age_seconds` of 0 leaves out HSTS, e.g. when
+// serving plain HTTP.
+#[derive(serde::Deserialize, Clone)]
+pub struct SecurityHeadersSettings {
+    pub content_security_policy: String,
+    pub csp_report_only: bool,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub hsts_max_age_seconds: u64,
+}
+
+// `/metrics` tells a lot about the traffic and the queue: scrapers have to send
+// `bearer_token` in an `Authorization: Bearer` header.
+#[derive(serde::Deserialize, Clone)]
+pub struct MetricsSettings {
+    pub bearer_token: Secret<String>,
+}
+
+// Where to export traces to, over OTLP/HTTP - e.g. `http://localhost:4318`.
+// Leave it out to only log.
+#[derive(serde::Deserialize, Clone)]
+pub struct OtlpSettings {
+    pub endpoint: String,
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -59,6 +204,8 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    // Apply the embedded migrations when the application starts.
+    pub migrate_on_startup: bool,
 }
 
 impl DatabaseSettings {
@@ -80,9 +227,250 @@ impl DatabaseSettings {
     }
 }
 
-pub fn get_configuration() -> Result<Settings, config::ConfigError> {
+// The attributes of the cookies we set: the session cookie, and the cookies that
+// `resign_cookies` swaps for ones signed with the current key. They are all
+// `HttpOnly`: no script of ours needs to read them.
+//
+// The flash message cookie only takes `path`: its other attributes are fixed by
+// `actix-web-flash-messages`, to the defaults below.
+#[derive(serde::Deserialize, Clone)]
+pub struct CookieSettings {
+    pub path: String,
+    pub secure: bool,
+    pub same_site: CookieSameSite,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(same_site: CookieSameSite) -> Self {
+        match same_site {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
+}
+
+impl CookieSettings {
+    pub fn apply(&self, cookie: &mut Cookie<'_>) {
+        cookie.set_path(self.path.clone());
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(true);
+        cookie.set_same_site(SameSite::from(self.same_site));
+    }
+}
+
+// `actix-web`'s `Key::from` refuses shorter secrets.
+const MIN_HMAC_SECRET_LENGTH: usize = 64;
+
+impl Settings {
+    /// Check the settings that would otherwise only fail once they are used, reporting
+    /// every problem at once.
+    pub fn validate(&self) -> Result<(), InvalidConfiguration> {
+        let mut problems = Vec::new();
+        let mut check = |key: &'static str, outcome: Result<(), String>| {
+            if let Err(problem) = outcome {
+                problems.push(InvalidSetting { key, problem });
+            }
+        };
+
+        check("application.base_url", check_http_url(&self.application.base_url));
+        check("application.hmac_secret", check_hmac_secret(&self.application.hmac_secret));
+        for previous in &self.application.previous_hmac_secrets {
+            check("application.previous_hmac_secrets", check_hmac_secret(previous));
+        }
+        check("email_client.base_url", check_http_url(&self.email_client.base_url));
+        check("email_client.sender_email", self.email_client.sender().map(|_| ()));
+        check("email_client.timeout_ms", check_positive(self.email_client.timeout_ms));
+        check(
+            "redis_uri",
+            redis::Client::open(self.redis_uri.expose_secret().as_str())
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        );
+        check(
+            "login_throttling.lockout_seconds",
+            check_positive(self.login_throttling.lockout_seconds),
+        );
+        check(
+            "subscription_throttling.ip_window_seconds",
+            check_positive(self.subscription_throttling.ip_window_seconds),
+        );
+        check(
+            "subscription_throttling.recipient_window_seconds",
+            check_positive(self.subscription_throttling.recipient_window_seconds),
+        );
+        let password_policy = &self.password_policy;
+        check(
+            "password_policy.min_length",
+            match (password_policy.min_length, password_policy.max_length) {
+                (min, max) if min > max => {
+                    Err(format!("{} is greater than password_policy.max_length ({})", min, max))
+                }
+                _ => Ok(()),
+            },
+        );
+        check(
+            "password_policy.min_strength_score",
+            match password_policy.min_strength_score {
+                Some(score) if score > 4 => {
+                    Err(format!("{} is above 4, the highest strength score", score))
+                }
+                _ => Ok(()),
+            },
+        );
+        check(
+            "password_hashing",
+            self.password_hashing.params().map(|_| ()).map_err(|e| e.to_string()),
+        );
+        check(
+            "idempotency.in_flight_timeout_ms",
+            check_positive(self.idempotency.in_flight_timeout_ms),
+        );
+        check("idempotency.ttl_hours", check_positive(self.idempotency.ttl_hours));
+        check(
+            "idempotency.cleanup_interval_seconds",
+            check_positive(self.idempotency.cleanup_interval_seconds),
+        );
+        check(
+            "idempotency.cleanup_batch_size",
+            check_positive(self.idempotency.cleanup_batch_size),
+        );
+        let security_headers = &self.security_headers;
+        check(
+            "security_headers.content_security_policy",
+            check_header_value(&security_headers.content_security_policy),
+        );
+        check(
+            "security_headers.frame_options",
+            match security_headers.frame_options.as_str() {
+                "DENY" | "SAMEORIGIN" => Ok(()),
+                other => Err(format!("{} is neither DENY nor SAMEORIGIN", other)),
+            },
+        );
+        check(
+            "security_headers.referrer_policy",
+            check_header_value(&security_headers.referrer_policy),
+        );
+        check(
+            "cookies.same_site",
+            match (self.cookies.same_site, self.cookies.secure) {
+                (CookieSameSite::None, false) => {
+                    Err("browsers reject SameSite=None cookies that are not secure".into())
+                }
+                _ => Ok(()),
+            },
+        );
+        check(
+            "metrics.bearer_token",
+            if self.metrics.bearer_token.expose_secret().trim().is_empty() {
+                Err("must not be empty".into())
+            } else {
+                Ok(())
+            },
+        );
+        if let Some(otlp) = &self.otlp {
+            check("otlp.endpoint", check_http_url(&otlp.endpoint));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfiguration(problems))
+        }
+    }
+}
+
+fn check_hmac_secret(secret: &Secret<String>) -> Result<(), String> {
+    match secret.expose_secret().len() {
+        n if n < MIN_HMAC_SECRET_LENGTH => {
+            Err(format!("must be at least {} bytes long, not {}", MIN_HMAC_SECRET_LENGTH, n))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_http_url(url: &str) -> Result<(), String> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if ["http", "https"].contains(&url.scheme()) => Ok(()),
+        Ok(_) => Err(format!("{} is not an http(s) URL", url)),
+        Err(e) => Err(format!("{} is not a valid URL: {}", url, e)),
+    }
+}
+
+fn check_header_value(value: &str) -> Result<(), String> {
+    HeaderValue::from_str(value)
+        .map(|_| ())
+        .map_err(|_| format!("{:?} is not a valid header value", value))
+}
+
+fn check_positive<T: Default + PartialOrd>(value: T) -> Result<(), String> {
+    if value > T::default() {
+        Ok(())
+    } else {
+        Err("must be greater than zero".into())
+    }
+}
+
+/// A setting whose value cannot work.
+#[derive(Debug)]
+pub struct InvalidSetting {
+    pub key: &'static str,
+    pub problem: String,
+}
+
+/// Every invalid setting found in the configuration.
+#[derive(thiserror::Error, Debug)]
+pub struct InvalidConfiguration(pub Vec<InvalidSetting>);
+
+impl std::fmt::Display for InvalidConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The configuration is invalid:")?;
+        for InvalidSetting { key, problem } in &self.0 {
+            write!(f, "\n  - {}: {}", key, problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigurationError {
+    #[error("Failed to determine the current directory.")]
+    CurrentDirectory(#[source] std::io::Error),
+    #[error("APP_ENVIRONMENT is invalid: {0}")]
+    UnsupportedEnvironment(String),
+    #[error("Failed to read {key} from {path}.")]
+    SecretFile {
+        key: &'static str,
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to load the configuration.")]
+    Load(#[from] config::ConfigError),
+    #[error(transparent)]
+    Invalid(#[from] InvalidConfiguration),
+}
+
+const SECRETS: [&str; 6] = [
+    "application.hmac_secret",
+    "application.previous_hmac_secrets",
+    "database.password",
+    "email_client.authorization_token",
+    "metrics.bearer_token",
+    "redis_uri",
+];
+
+pub fn get_configuration() -> Result<Settings, ConfigurationError> {
     // Initialize our configuration reader
-    let base_path = std::env::current_dir().expect("Failed to determine the current directory");
+    let base_path = std::env::current_dir().map_err(ConfigurationError::CurrentDirectory)?;
     let configuration_directory = base_path.join("configuration");
 
     // Detect the running environment
@@ -90,17 +478,29 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let environment: Environment = std::env::var("APP_ENVIRONMENT")
         .unwrap_or_else(|_| "local".into())
         .try_into()
-        .expect("APP_ENVIRONMENT must be 'local' or 'production'");
+        .map_err(ConfigurationError::UnsupportedEnvironment)?;
 
     let environment_filename = format!("{}.yml", environment.as_str());
 
-    let settings = config::Config::builder()
+    let mut builder = config::Config::builder()
         .add_source(config::File::from(configuration_directory.join("base")))
         .add_source(config::File::from(configuration_directory.join(environment_filename)))
-        .add_source(config::Environment::with_prefix("app").prefix_separator("_").separator("__"))
-        .build()?;
+        .add_source(config::Environment::with_prefix("app").prefix_separator("_").separator("__"));
+
+    // Each secret can be read from the file `<key>_file` points to instead, e.g. a
+    // Docker or Kubernetes secret mount. The file wins over any other source.
+    let settings = builder.build_cloned()?;
+    for key in SECRETS {
+        if let Ok(path) = settings.get_string(&format!("{}_file", key)) {
+            let secret = std::fs::read_to_string(&path)
+                .map_err(|source| ConfigurationError::SecretFile { key, path, source })?;
+            builder = builder.set_override(key, secret.trim_end_matches(['\r', '\n']))?;
+        }
+    }
 
-    settings.try_deserialize::<Settings>()
+    let settings = builder.build()?.try_deserialize::<Settings>()?;
+    settings.validate()?;
+    Ok(settings)
 }
 
 // The possible runtime environment for our application
@@ -132,3 +532,140 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use claim::{assert_err, assert_ok};
+    use config::{Config, File, FileFormat};
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::{CookieSameSite, Settings};
+
+    fn local_settings() -> Settings {
+        Config::builder()
+            .add_source(File::from_str(include_str!("../configuration/base.yml"), FileFormat::Yaml))
+            .add_source(File::from_str(
+                include_str!("../configuration/local.yml"),
+                FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    fn invalid_keys(settings: &Settings) -> Vec<&'static str> {
+        assert_err!(settings.validate()).0.into_iter().map(|s| s.key).collect()
+    }
+
+    #[test]
+    fn the_local_configuration_is_valid() {
+        assert_ok!(local_settings().validate());
+    }
+
+    #[test]
+    fn a_short_hmac_secret_is_rejected() {
+        let mut settings = local_settings();
+        settings.application.hmac_secret = Secret::new("too-short".into());
+        assert_eq!(invalid_keys(&settings), vec!["application.hmac_secret"]);
+    }
+
+    #[test]
+    fn security_headers_that_cannot_be_sent_are_rejected() {
+        let mut settings = local_settings();
+        settings.security_headers.frame_options = "ALLOW-FROM https://example.com".into();
+        settings.security_headers.content_security_policy = "default-src 'self'\n".into();
+        assert_eq!(
+            invalid_keys(&settings),
+            vec!["security_headers.content_security_policy", "security_headers.frame_options"]
+        );
+    }
+
+    #[test]
+    fn a_password_policy_no_password_can_meet_is_rejected() {
+        let mut settings = local_settings();
+        settings.password_policy.min_length = 64;
+        settings.password_policy.max_length = 32;
+        settings.password_policy.min_strength_score = Some(5);
+        assert_eq!(
+            invalid_keys(&settings),
+            vec!["password_policy.min_length", "password_policy.min_strength_score"]
+        );
+    }
+
+    #[test]
+    fn insecure_cross_site_cookies_are_rejected() {
+        let mut settings = local_settings();
+        settings.cookies.same_site = CookieSameSite::None;
+        settings.cookies.secure = false;
+        assert_eq!(invalid_keys(&settings), vec!["cookies.same_site"]);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut settings = local_settings();
+        settings.application.base_url = "127.0.0.1".into();
+        settings.email_client.sender_email = "not-an-email".into();
+        settings.email_client.timeout_ms = 0;
+        settings.redis_uri = Secret::new("localhost:6379".into());
+        assert_eq!(
+            invalid_keys(&settings),
+            vec![
+                "application.base_url",
+                "email_client.sender_email",
+                "email_client.timeout_ms",
+                "redis_uri"
+            ]
+        );
+    }
+
+    #[test]
+    fn previous_hmac_secrets_can_be_given_as_a_single_string() {
+        let settings: Settings = Config::builder()
+            .add_source(File::from_str(include_str!("../configuration/base.yml"), FileFormat::Yaml))
+            .add_source(File::from_str(
+                include_str!("../configuration/local.yml"),
+                FileFormat::Yaml,
+            ))
+            .set_override("application.previous_hmac_secrets", "first-secret\nsecond-secret\n")
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let secrets: Vec<_> = settings
+            .application
+            .previous_hmac_secrets
+            .iter()
+            .map(|s| s.expose_secret().as_str())
+            .collect();
+        assert_eq!(secrets, vec!["first-secret", "second-secret"]);
+    }
+
+    #[test]
+    fn trusted_proxies_can_be_given_as_a_single_string() {
+        let settings: Settings = Config::builder()
+            .add_source(File::from_str(include_str!("../configuration/base.yml"), FileFormat::Yaml))
+            .add_source(File::from_str(
+                include_str!("../configuration/local.yml"),
+                FileFormat::Yaml,
+            ))
+            .set_override("application.trusted_proxies", "10.0.0.1 ::1")
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let expected: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        assert_eq!(settings.application.trusted_proxies, expected);
+    }
+
+    #[test]
+    fn urls_must_use_http() {
+        let mut settings = local_settings();
+        settings.email_client.base_url = "ftp://example.com".into();
+        assert_eq!(invalid_keys(&settings), vec!["email_client.base_url"]);
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::body::MessageBody;
+use actix_web::cookie::{Cookie, CookieJar, Key};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, COOKIE, SET_COOKIE};
+use actix_web::web;
+use actix_web_lab::middleware::Next;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::configuration::CookieSettings;
+
+/// The keys our cookies are signed or encrypted with.
+///
+/// New cookies only ever use the current key; the previous ones are kept around while
+/// a rotation is in progress, so that the cookies they produced keep working.
+#[derive(Clone)]
+pub struct CookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl CookieKeys {
+    pub fn new(current: &Secret<String>, previous: &[Secret<String>]) -> Self {
+        let key = |secret: &Secret<String>| Key::from(secret.expose_secret().as_bytes());
+        Self { current: key(current), previous: previous.iter().map(key).collect() }
+    }
+
+    pub fn current(&self) -> Key {
+        self.current.clone()
+    }
+
+    // Returns `cookie` signed or encrypted - whichever it was - with the current key,
+    // if a previous key produced it. Cookies that the current key produced, or that
+    // none of our keys did, are left alone.
+    fn resign(
+        &self,
+        cookie: &Cookie<'static>,
+        attributes: &CookieSettings,
+    ) -> Option<Cookie<'static>> {
+        let name = cookie.name();
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie.clone());
+        if jar.private(&self.current).get(name).is_some()
+            || jar.signed(&self.current).get(name).is_some()
+        {
+            return None;
+        }
+
+        let mut resigned = CookieJar::new();
+        for key in &self.previous {
+            if let Some(plain) = jar.private(key).get(name) {
+                resigned.private_mut(&self.current).add(plain);
+                break;
+            }
+            if let Some(plain) = jar.signed(key).get(name) {
+                resigned.signed_mut(&self.current).add(plain);
+                break;
+            }
+        }
+        let mut resigned = resigned.get(name)?.clone();
+        attributes.apply(&mut resigned);
+        Some(resigned)
+    }
+}
+
+/// Swap cookies produced by a previous key for ones produced by the current key,
+/// both in the request - for the middlewares that read them - and in the response,
+/// so that the client stops sending the old ones.
+///
+/// It has to wrap every middleware that reads cookies: they are only parsed once.
+pub async fn resign_cookies(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let keys = req.app_data::<web::Data<CookieKeys>>().cloned();
+    let attributes = req.app_data::<web::Data<CookieSettings>>().cloned();
+    let mut resigned = Vec::new();
+    // Nothing to do unless a rotation is in progress.
+    if let (Some(keys), Some(attributes)) =
+        (keys.filter(|keys| !keys.previous.is_empty()), attributes)
+    {
+        let cookies: Vec<_> = req
+            .headers()
+            .get_all(COOKIE)
+            .filter_map(|header| header.to_str().ok())
+            .flat_map(|header| header.split(';'))
+            .filter_map(|pair| Cookie::parse_encoded(pair.trim().to_owned()).ok())
+            .map(|cookie| match keys.resign(&cookie, &attributes) {
+                Some(cookie) => {
+                    resigned.push(cookie.clone());
+                    cookie
+                }
+                None => cookie,
+            })
+            .collect();
+        if !resigned.is_empty() {
+            let header = cookies
+                .iter()
+                .map(|cookie| cookie.encoded().stripped().to_string())
+                .collect::<Vec<_>>()
+                .join("; ");
+            req.headers_mut().insert(COOKIE, HeaderValue::from_str(&header)?);
+        }
+    }
+
+    let mut response = next.call(req).await?;
+    for cookie in resigned {
+        // The response may already replace or remove the cookie.
+        if response.response().cookies().all(|c| c.name() != cookie.name()) {
+            let header = HeaderValue::from_str(&cookie.encoded().to_string())?;
+            response.headers_mut().append(SET_COOKIE, header);
+        }
+    }
+    Ok(response)
+}
@@ -0,0 +1,109 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::HeaderName;
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
+use actix_web_lab::middleware::Next;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use crate::authentication::bearer_token;
+use crate::session_state::TypedSession;
+use crate::utils::{bytes_to_payload, constant_time_eq, e500};
+
+// Lets scripts send the token without touching the body of the request.
+pub const CSRF_TOKEN_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+/// The CSRF token of the session, for the forms of the page being rendered.
+/// Handlers below the protected scope get it as `web::ReqData<CsrfToken>`.
+#[derive(Clone)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// The hidden form field carrying the token, which `templates/csrf_field.html` renders.
+#[derive(serde::Deserialize)]
+struct CsrfTokenForm {
+    csrf_token: Option<String>,
+}
+
+/// Protect the forms below a scope against cross-site request forgery with
+/// synchronizer tokens.
+///
+/// Every session gets a random token at login, which the pages below the scope render
+/// as a hidden field of their forms. Requests that may change state have to send it
+/// back, in that field or in an `X-CSRF-Token` header, or they get a 403. Requests
+/// authenticated with an API token carry no ambient credentials and are let through.
+///
+/// Wrap it inside the authentication middleware, so that anonymous users are sent to
+/// the login page rather than turned away.
+pub async fn verify_csrf_token(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if matches!(bearer_token(req.headers()), Ok(Some(_))) {
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let expected = session.get_csrf_token().map_err(e500)?;
+    if !req.method().is_safe() {
+        let sent = sent_csrf_token(&mut req).await?;
+        let is_valid = match (&expected, &sent) {
+            (Some(expected), Some(sent)) => constant_time_eq(expected.as_bytes(), sent.as_bytes()),
+            _ => false,
+        };
+        if !is_valid {
+            let response = HttpResponse::Forbidden()
+                .body("This form has expired. Reload the page and try again.");
+            let e = anyhow::anyhow!("The request carries no valid CSRF token");
+            return Err(InternalError::from_response(e, response).into());
+        }
+    }
+    // Sessions get their token at login; this covers the ones that predate it.
+    let token = match expected {
+        Some(token) => token,
+        None => {
+            let token = generate_csrf_token();
+            session.insert_csrf_token(&token).map_err(e500)?;
+            token
+        }
+    };
+
+    req.extensions_mut().insert(CsrfToken(token));
+    next.call(req).await
+}
+
+// Reads the token from the header or, failing that, from the form in the body.
+async fn sent_csrf_token(req: &mut ServiceRequest) -> Result<Option<String>, actix_web::Error> {
+    if let Some(header) = req.headers().get(CSRF_TOKEN_HEADER) {
+        return Ok(header.to_str().ok().map(str::to_owned));
+    }
+    if req.content_type() != "application/x-www-form-urlencoded" {
+        return Ok(None);
+    }
+    // The handler needs the body too: hand a copy back to it.
+    let body = req.extract::<web::Bytes>().await?;
+    let form = serde_urlencoded::from_bytes::<CsrfTokenForm>(&body);
+    req.set_payload(bytes_to_payload(body));
+    Ok(form.ok().and_then(|form| form.csrf_token))
+}
+
+/// Generate the CSRF token of a new session.
+pub fn generate_csrf_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric)).map(char::from).take(32).collect()
+}
@@ -1,7 +1,10 @@
 use crate::domain::subscriber_email::SubscriberEmail;
 use crate::domain::subscriber_name::SubscriberName;
+use crate::i18n::Locale;
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    // The language of the emails we send them.
+    pub locale: Locale,
 }
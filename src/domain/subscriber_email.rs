@@ -1,13 +1,15 @@
 use validator::validate_email;
 
+use crate::i18n::{Message, MessageId};
+
 #[derive(Debug, Clone)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<SubscriberEmail, String> {
+    pub fn parse(s: String) -> Result<SubscriberEmail, Message> {
         match validate_email(&s) {
             true => Ok(Self(s)),
-            false => Err(format!("`{}` is not a valid email address.", s)),
+            false => Err(Message::new(MessageId::InvalidSubscriberEmail).with("email", s)),
         }
     }
 }
@@ -1,3 +1,5 @@
+use crate::i18n::{Message, MessageId};
+
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
@@ -8,7 +10,7 @@ impl AsRef<str> for SubscriberName {
 }
 
 impl SubscriberName {
-    pub fn parse(s: String) -> Result<SubscriberName, String> {
+    pub fn parse(s: String) -> Result<SubscriberName, Message> {
         let name = s.trim().to_string();
         let is_empty_or_whitespace = name.trim().is_empty();
         let is_too_long = name.len() > 256;
@@ -18,11 +20,9 @@ impl SubscriberName {
             name.chars().any(|char| forbidden_characters.contains(&char));
         // If any of the above checks returns true, return an error.
         if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err(format!(
-                "`{}` is not a valid subscriber name. Subscriber name cannot be empty, more than \
-                 256 characters long, or contain the following characters: {:?}",
-                name, forbidden_characters,
-            ))
+            Err(Message::new(MessageId::InvalidSubscriberName)
+                .with("name", name)
+                .with("forbidden", format!("{:?}", forbidden_characters)))
         } else {
             Ok(Self(name))
         }
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use actix_web::HttpRequest;
+use once_cell::sync::Lazy;
+
+/// The languages subscribers can read our pages and emails in.
+///
+/// Each locale has a catalog in `locales/<locale>.json`, mapping every `MessageId`
+/// to its text. `{placeholders}` in the text are filled in with the arguments of
+/// the `Message`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Fr];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+
+    // Accepts language tags like `fr` or `fr-CH`: only the language matters to us.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        Self::ALL.into_iter().find(|l| l.as_str().eq_ignore_ascii_case(language))
+    }
+
+    /// The locale the client prefers among ours, according to an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Locale, f32)> = None;
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let Some(locale) = parts.next().and_then(Self::parse) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or(0.0);
+            // Ties go to the range listed first.
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((locale, quality));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+
+    /// The locale to use for `request`: the one picked in the form, if any, then the
+    /// one preferred by the browser, then our default.
+    pub fn negotiate(form_field: Option<&str>, request: &HttpRequest) -> Self {
+        form_field
+            .and_then(Self::parse)
+            .or_else(|| {
+                request
+                    .headers()
+                    .get(ACCEPT_LANGUAGE)
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(Self::from_accept_language)
+            })
+            .unwrap_or_default()
+    }
+
+    // Shorthand for the text of a message without arguments, used by the templates.
+    pub fn translate(self, id: MessageId) -> String {
+        Message::new(id).localize(self)
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// The texts shown to subscribers, identified by their key in the catalogs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageId {
+    InvalidSubscriberName,
+    InvalidSubscriberEmail,
+    AlreadySubscribed,
+    TooManySubscriptionRequests,
+    UnexpectedError,
+    InvalidSubscriptionToken,
+    ConfirmationEmailSubject,
+    ConfirmationEmailHtml,
+    ConfirmationEmailText,
+    HomeTitle,
+    HomeWelcome,
+    HomeName,
+    HomeEmail,
+    HomeSubscribe,
+    SubscriptionConfirmedTitle,
+    SubscriptionConfirmed,
+}
+
+impl MessageId {
+    pub const ALL: [MessageId; 16] = [
+        MessageId::InvalidSubscriberName,
+        MessageId::InvalidSubscriberEmail,
+        MessageId::AlreadySubscribed,
+        MessageId::TooManySubscriptionRequests,
+        MessageId::UnexpectedError,
+        MessageId::InvalidSubscriptionToken,
+        MessageId::ConfirmationEmailSubject,
+        MessageId::ConfirmationEmailHtml,
+        MessageId::ConfirmationEmailText,
+        MessageId::HomeTitle,
+        MessageId::HomeWelcome,
+        MessageId::HomeName,
+        MessageId::HomeEmail,
+        MessageId::HomeSubscribe,
+        MessageId::SubscriptionConfirmedTitle,
+        MessageId::SubscriptionConfirmed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageId::InvalidSubscriberName => "invalid-subscriber-name",
+            MessageId::InvalidSubscriberEmail => "invalid-subscriber-email",
+            MessageId::AlreadySubscribed => "already-subscribed",
+            MessageId::TooManySubscriptionRequests => "too-many-subscription-requests",
+            MessageId::UnexpectedError => "unexpected-error",
+            MessageId::InvalidSubscriptionToken => "invalid-subscription-token",
+            MessageId::ConfirmationEmailSubject => "confirmation-email-subject",
+            MessageId::ConfirmationEmailHtml => "confirmation-email-html",
+            MessageId::ConfirmationEmailText => "confirmation-email-text",
+            MessageId::HomeTitle => "home-title",
+            MessageId::HomeWelcome => "home-welcome",
+            MessageId::HomeName => "home-name",
+            MessageId::HomeEmail => "home-email",
+            MessageId::HomeSubscribe => "home-subscribe",
+            MessageId::SubscriptionConfirmedTitle => "subscription-confirmed-title",
+            MessageId::SubscriptionConfirmed => "subscription-confirmed",
+        }
+    }
+}
+
+/// A message to show to a subscriber, rendered in their locale with `localize`.
+///
+/// It displays in English, for the logs and the API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    id: MessageId,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(id: MessageId) -> Self {
+        Self { id, args: Vec::new() }
+    }
+
+    // Sets the value of the `{name}` placeholder.
+    pub fn with(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.args.push((name, value.into()));
+        self
+    }
+
+    pub fn localize(&self, locale: Locale) -> String {
+        let text = CATALOGS
+            .get(&locale)
+            .and_then(|catalog| catalog.get(self.id.as_str()))
+            .or_else(|| CATALOGS[&Locale::En].get(self.id.as_str()))
+            .map_or(self.id.as_str(), String::as_str);
+        fill_placeholders(text, &self.args)
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.localize(Locale::En).fmt(f)
+    }
+}
+
+static CATALOGS: Lazy<HashMap<Locale, HashMap<String, String>>> = Lazy::new(|| {
+    let sources = [
+        (Locale::En, include_str!("../locales/en.json")),
+        (Locale::Fr, include_str!("../locales/fr.json")),
+    ];
+    sources
+        .into_iter()
+        .map(|(locale, source)| {
+            let catalog = serde_json::from_str(source)
+                .unwrap_or_else(|e| panic!("The {} catalog is not valid: {}", locale, e));
+            (locale, catalog)
+        })
+        .collect()
+});
+
+// Replaces the `{name}` placeholders of `text` in a single pass, so that braces in
+// the values are left as they are.
+fn fill_placeholders(text: &str, args: &[(&'static str, String)]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('}')
+            .and_then(|end| Some((args.iter().find(|(name, _)| *name == &after[..end])?, end)));
+        match value {
+            Some(((_, value), end)) => {
+                output.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn placeholders(text: &str) -> BTreeSet<&str> {
+        text.split('{').skip(1).filter_map(|s| s.split_once('}')).map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn every_catalog_has_every_message_with_the_same_placeholders() {
+        let english = &CATALOGS[&Locale::En];
+        for locale in Locale::ALL {
+            let catalog = &CATALOGS[&locale];
+            assert_eq!(catalog.len(), MessageId::ALL.len(), "{} has unknown messages", locale);
+            for id in MessageId::ALL {
+                let text = catalog
+                    .get(id.as_str())
+                    .unwrap_or_else(|| panic!("{} is missing {}", locale, id.as_str()));
+                assert_eq!(placeholders(text), placeholders(&english[id.as_str()]), "{:?}", id);
+            }
+        }
+    }
+
+    #[test]
+    fn the_preferred_supported_language_is_picked() {
+        assert_eq!(Locale::from_accept_language("fr-CH, fr;q=0.9, en;q=0.8"), Some(Locale::Fr));
+        assert_eq!(Locale::from_accept_language("de, en;q=0.5, fr;q=0.7"), Some(Locale::Fr));
+        assert_eq!(Locale::from_accept_language("en, fr"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("fr;q=0, en;q=0.1"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("de, *;q=0.5"), None);
+        assert_eq!(Locale::from_accept_language(""), None);
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_once() {
+        let message = Message::new(MessageId::InvalidSubscriberEmail).with("email", "{email}");
+        assert_eq!(message.localize(Locale::En), "`{email}` is not a valid email address.");
+        assert_eq!(
+            message.localize(Locale::Fr),
+            "« {email} » n'est pas une adresse e-mail valide."
+        );
+    }
+}
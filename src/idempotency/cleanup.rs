@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use super::persistence::expiry_cutoff;
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::startup::get_connection_pool;
+
+// Deletes expired idempotency keys, together with the responses saved for them.
+// Rows go in batches of `cleanup_batch_size`, so that a large backlog does not hold
+// locks for long. Returns the number of rows purged.
+#[tracing::instrument(skip_all, fields(n_purged = tracing::field::Empty), err)]
+pub async fn delete_expired_idempotency_keys(
+    pool: &PgPool,
+    settings: &IdempotencySettings,
+) -> Result<u64, anyhow::Error> {
+    let cutoff = expiry_cutoff(settings);
+    let mut n_purged = 0;
+    loop {
+        let n_deleted = sqlx::query!(
+            r#"
+            DELETE FROM idempotency
+            WHERE ctid IN (
+                SELECT ctid
+                FROM idempotency
+                WHERE created_at < $1
+                LIMIT $2
+                FOR UPDATE
+                SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            settings.cleanup_batch_size,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        n_purged += n_deleted;
+        if n_deleted < settings.cleanup_batch_size as u64 {
+            break;
+        }
+    }
+    tracing::Span::current().record("n_purged", n_purged);
+    Ok(n_purged)
+}
+
+async fn cleanup_loop(pool: PgPool, settings: IdempotencySettings) -> Result<(), anyhow::Error> {
+    loop {
+        // Failures are logged by `delete_expired_idempotency_keys`: the next run
+        // picks up whatever was left behind.
+        let _ = delete_expired_idempotency_keys(&pool, &settings).await;
+        tokio::time::sleep(Duration::from_secs(settings.cleanup_interval_seconds)).await;
+    }
+}
+
+pub async fn run_cleanup_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    cleanup_loop(connection_pool, configuration.idempotency).await
+}
@@ -0,0 +1,48 @@
+use actix_web::http::Method;
+use sha2::{Digest, Sha256};
+
+// A digest of the request an idempotency key was first used for.
+// Reusing a key for a different request is a client bug: it must be reported rather
+// than answered with the response to the original request.
+#[derive(Debug, PartialEq)]
+pub struct RequestFingerprint(String);
+
+impl RequestFingerprint {
+    // Neither methods nor paths can contain a line break, so the separators below
+    // keep the three parts from bleeding into each other.
+    pub fn new(method: &Method, path: &str, body: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(method.as_str().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(path.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(body);
+        Self(format!("{:x}", hasher.finalize()))
+    }
+}
+
+impl AsRef<str> for RequestFingerprint {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_requests_share_a_fingerprint() {
+        let a = RequestFingerprint::new(&Method::POST, "/subscriptions", b"name=a");
+        let b = RequestFingerprint::new(&Method::POST, "/subscriptions", b"name=a");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn method_path_and_body_all_contribute_to_the_fingerprint() {
+        let original = RequestFingerprint::new(&Method::POST, "/subscriptions", b"name=a");
+        assert_ne!(original, RequestFingerprint::new(&Method::PUT, "/subscriptions", b"name=a"));
+        assert_ne!(original, RequestFingerprint::new(&Method::POST, "/newsletters", b"name=a"));
+        assert_ne!(original, RequestFingerprint::new(&Method::POST, "/subscriptions", b"name=b"));
+    }
+}
@@ -0,0 +1,115 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use sqlx::PgPool;
+
+use super::transaction::ClaimTransaction;
+use super::{
+    save_response, try_processing, IdempotencyKey, IdempotencyKeyReused, IdempotencyOwner,
+    NextAction, RequestFingerprint, RequestInFlight,
+};
+use crate::authentication::UserId;
+use crate::configuration::IdempotencySettings;
+use crate::utils::{bytes_to_payload, client_ip, e400, e422, e500};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+// Tells the client it is looking at the response to an earlier request.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+#[derive(serde::Deserialize)]
+struct IdempotencyKeyForm {
+    idempotency_key: Option<String>,
+}
+
+// Makes POST requests carrying an idempotency key safe to retry: the first response
+// is stored and replayed to every later request with the same key.
+// The key is read from the `Idempotency-Key` header or, for browser forms that cannot
+// set headers, from the `idempotency_key` field of the form. The header wins if both
+// are sent.
+// Keys belong to the authenticated user if there is one - wrap this middleware
+// inside the authentication middleware - and to the client address otherwise.
+// Reusing a key for a different method, path or body gets a 422.
+pub async fn handle_idempotency_key(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let idempotency_key = match sent_idempotency_key(&mut req).await? {
+        Some(key) => IdempotencyKey::try_from(key).map_err(e400)?,
+        None => return next.call(req).await.map(ServiceResponse::map_into_boxed_body),
+    };
+    let owner = match req.extensions().get::<UserId>() {
+        Some(user_id) => IdempotencyOwner::User(**user_id),
+        None => IdempotencyOwner::Client(client_ip(req.request())),
+    };
+    // The body has to be read to be fingerprinted: hand a copy back to the handler.
+    let body = req.extract::<web::Bytes>().await?;
+    let fingerprint = RequestFingerprint::new(req.method(), req.path(), &body);
+    req.set_payload(bytes_to_payload(body));
+    let pool = req.app_data::<web::Data<PgPool>>().ok_or_else(|| e500("Missing PgPool"))?.clone();
+    let settings = req
+        .app_data::<web::Data<IdempotencySettings>>()
+        .ok_or_else(|| e500("Missing IdempotencySettings"))?
+        .clone();
+
+    let transaction = match try_processing(&pool, &idempotency_key, &owner, &fingerprint, &settings)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::RejectMismatchedRequest => return Err(e422(IdempotencyKeyReused)),
+        NextAction::RetryLater => return Err(RequestInFlight.into()),
+        NextAction::ReturnSavedResponse(mut saved_response) => {
+            saved_response
+                .headers_mut()
+                .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+            return Ok(req.into_response(saved_response));
+        }
+    };
+
+    // The handler stores the effects of the request in the same transaction, through
+    // `RequestTransaction`, so that they are committed along with the saved response.
+    let claim = ClaimTransaction::new(transaction);
+    req.extensions_mut().insert(claim.clone());
+    let response = next.call(req).await;
+    let transaction = claim.take();
+    let (request, response) = response?.into_parts();
+    // Failures on our side and rejections that only hold for now (rate limiting,
+    // timeouts) are not stored: dropping the transaction releases the key, so that the
+    // client can retry. A handler that dropped the transaction has released it already.
+    let transaction = match transaction {
+        Some(transaction) if !is_transient(response.status()) => transaction,
+        _ => return Ok(ServiceResponse::new(request, response.map_into_boxed_body())),
+    };
+    let response =
+        save_response(transaction, &idempotency_key, &owner, response.map_into_boxed_body())
+            .await
+            .map_err(e500)?;
+    Ok(ServiceResponse::new(request, response))
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error()
+        || matches!(status, StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS)
+}
+
+async fn sent_idempotency_key(
+    req: &mut ServiceRequest,
+) -> Result<Option<String>, actix_web::Error> {
+    if req.method() != Method::POST {
+        return Ok(None);
+    }
+    if let Some(header) = req.headers().get(IDEMPOTENCY_KEY) {
+        return Ok(Some(header.to_str().map_err(e400)?.to_owned()));
+    }
+    if req.content_type() != "application/x-www-form-urlencoded" {
+        return Ok(None);
+    }
+    // The handler needs the body too: hand a copy back to it.
+    let body = req.extract::<web::Bytes>().await?;
+    let form = serde_urlencoded::from_bytes::<IdempotencyKeyForm>(&body);
+    req.set_payload(bytes_to_payload(body));
+    Ok(form.ok().and_then(|form| form.idempotency_key))
+}
@@ -1,4 +1,15 @@
+mod cleanup;
+mod fingerprint;
 mod key;
+mod middleware;
 mod persistence;
+mod transaction;
+pub use cleanup::{delete_expired_idempotency_keys, run_cleanup_until_stopped};
+pub use fingerprint::RequestFingerprint;
 pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response, try_processing, NextAction};
+pub use middleware::{handle_idempotency_key, IDEMPOTENCY_KEY, IDEMPOTENT_REPLAYED};
+pub use persistence::{
+    get_saved_response, save_response, try_processing, IdempotencyKeyReused, IdempotencyOwner,
+    NextAction, RequestInFlight,
+};
+pub use transaction::RequestTransaction;
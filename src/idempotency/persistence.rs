@@ -1,11 +1,17 @@
 use actix_web::body::to_bytes;
+use actix_web::http::header::RETRY_AFTER;
 use actix_web::http::StatusCode;
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
 use sqlx::postgres::PgHasArrayType;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use super::IdempotencyKey;
+use super::{IdempotencyKey, RequestFingerprint};
+use crate::configuration::IdempotencySettings;
+
+// Postgres error code raised when a statement gives up waiting on a lock.
+const LOCK_NOT_AVAILABLE: &str = "55P03";
 
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
@@ -14,6 +20,36 @@ struct HeaderPairRecord {
     value: Vec<u8>,
 }
 
+// Who an idempotency key belongs to: keys are only unique per owner.
+#[derive(Debug, Clone)]
+pub enum IdempotencyOwner {
+    User(Uuid),
+    // Anonymous requests are attributed to the client address they come from.
+    Client(String),
+}
+
+impl IdempotencyOwner {
+    fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Self::User(user_id) => Some(*user_id),
+            Self::Client(_) => None,
+        }
+    }
+
+    fn client_id(&self) -> Option<&str> {
+        match self {
+            Self::User(_) => None,
+            Self::Client(client_id) => Some(client_id),
+        }
+    }
+}
+
+impl From<Uuid> for IdempotencyOwner {
+    fn from(user_id: Uuid) -> Self {
+        Self::User(user_id)
+    }
+}
+
 impl PgHasArrayType for HeaderPairRecord {
     fn array_type_info() -> sqlx::postgres::PgTypeInfo {
         sqlx::postgres::PgTypeInfo::with_name("_header_pair")
@@ -23,7 +59,7 @@ impl PgHasArrayType for HeaderPairRecord {
 pub async fn get_saved_response(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
+    owner: &IdempotencyOwner,
 ) -> Result<Option<HttpResponse>, anyhow::Error> {
     let saved_response = sqlx::query!(
         r#"
@@ -33,10 +69,13 @@ pub async fn get_saved_response(
             response_body as "response_body!"
             FROM idempotency
             WHERE
-            user_id = $1 AND
-            idempotency_key = $2
+            user_id IS NOT DISTINCT FROM $1 AND
+            client_id IS NOT DISTINCT FROM $2 AND
+            idempotency_key = $3 AND
+            response_status_code IS NOT NULL
         "#,
-        user_id,
+        owner.user_id(),
+        owner.client_id(),
         idempotency_key.as_ref()
     )
     .fetch_optional(pool)
@@ -57,7 +96,7 @@ pub async fn get_saved_response(
 pub async fn save_response(
     mut transaction: Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
+    owner: &IdempotencyOwner,
     http_response: HttpResponse,
 ) -> Result<HttpResponse, anyhow::Error> {
     let (response_head, body) = http_response.into_parts();
@@ -76,14 +115,16 @@ pub async fn save_response(
         r#"
             UPDATE idempotency
             SET
-            response_status_code = $3,
-            response_headers = $4,
-            response_body = $5
+            response_status_code = $4,
+            response_headers = $5,
+            response_body = $6
             WHERE
-            user_id = $1 AND
-            idempotency_key = $2
+            user_id IS NOT DISTINCT FROM $1 AND
+            client_id IS NOT DISTINCT FROM $2 AND
+            idempotency_key = $3
         "#,
-        user_id,
+        owner.user_id(),
+        owner.client_id(),
         idempotency_key.as_ref(),
         status_code,
         headers,
@@ -97,40 +138,145 @@ pub async fn save_response(
     Ok(http_response)
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("The idempotency key has already been used for a different request.")]
+pub struct IdempotencyKeyReused;
+
+#[derive(thiserror::Error, Debug)]
+#[error("A request with the same idempotency key is still being processed.")]
+pub struct RequestInFlight;
+
+impl ResponseError for RequestInFlight {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::CONFLICT
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header((RETRY_AFTER, "1"))
+            .body(self.to_string())
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
+    // The key has already been used for a different request.
+    RejectMismatchedRequest,
+    // Another request with the same key did not complete within the configured timeout.
+    RetryLater,
 }
 
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
+    owner: &IdempotencyOwner,
+    fingerprint: &RequestFingerprint,
+    settings: &IdempotencySettings,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let n_inserted_rows = sqlx::query!(
+    // If a concurrent request holds the same key, claiming it waits for its
+    // transaction to commit - with the response saved - or to roll back.
+    // The wait is bounded so that a slow request cannot pile up connections.
+    sqlx::query!(
+        "SELECT set_config('lock_timeout', $1, true)",
+        format!("{}ms", settings.in_flight_timeout_ms)
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let inserted = claim_key(&mut transaction, idempotency_key, owner, fingerprint, settings).await;
+    let n_inserted_rows = match inserted {
+        Ok(n_inserted_rows) => n_inserted_rows,
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(LOCK_NOT_AVAILABLE) => {
+            return Ok(NextAction::RetryLater);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // The timeout only applies to waiting on other requests, not to the processing
+    // of this one.
+    sqlx::query!("RESET lock_timeout").execute(&mut *transaction).await?;
+
+    if n_inserted_rows > 0 {
+        Ok(NextAction::StartProcessing(transaction))
+    } else {
+        let stored_fingerprint = sqlx::query!(
+            r#"
+                SELECT request_fingerprint
+                FROM idempotency
+                WHERE
+                user_id IS NOT DISTINCT FROM $1 AND
+                client_id IS NOT DISTINCT FROM $2 AND
+                idempotency_key = $3
+            "#,
+            owner.user_id(),
+            owner.client_id(),
+            idempotency_key.as_ref()
+        )
+        .fetch_optional(pool)
+        .await?
+        .and_then(|r| r.request_fingerprint);
+        if stored_fingerprint.is_some_and(|f| f != fingerprint.as_ref()) {
+            return Ok(NextAction::RejectMismatchedRequest);
+        }
+        // The row may lack a response, or be gone, if the other request went away
+        // in the meantime: the client has to try again.
+        match get_saved_response(pool, idempotency_key, owner).await? {
+            Some(saved_response) => Ok(NextAction::ReturnSavedResponse(saved_response)),
+            None => Ok(NextAction::RetryLater),
+        }
+    }
+}
+
+// Inserts a row for the key, unless a live one exists already.
+// Returns the number of rows inserted.
+async fn claim_key(
+    transaction: &mut Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    owner: &IdempotencyOwner,
+    fingerprint: &RequestFingerprint,
+    settings: &IdempotencySettings,
+) -> Result<u64, sqlx::Error> {
+    // An expired key is up for grabs again, whatever request it was used for.
+    sqlx::query!(
+        r#"
+            DELETE FROM idempotency
+            WHERE
+            user_id IS NOT DISTINCT FROM $1 AND
+            client_id IS NOT DISTINCT FROM $2 AND
+            idempotency_key = $3 AND
+            created_at < $4
+        "#,
+        owner.user_id(),
+        owner.client_id(),
+        idempotency_key.as_ref(),
+        expiry_cutoff(settings),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let outcome = sqlx::query!(
         r#"
             INSERT INTO idempotency (
             user_id,
+            client_id,
             idempotency_key,
+            request_fingerprint,
             created_at
             )
-            VALUES ($1, $2, now())
+            VALUES ($1, $2, $3, $4, now())
             ON CONFLICT DO NOTHING
         "#,
-        user_id,
-        idempotency_key.as_ref()
+        owner.user_id(),
+        owner.client_id(),
+        idempotency_key.as_ref(),
+        fingerprint.as_ref()
     )
-    .execute(&mut *transaction)
-    .await?
-    .rows_affected();
+    .execute(&mut **transaction)
+    .await?;
+    Ok(outcome.rows_affected())
+}
 
-    if n_inserted_rows > 0 {
-        Ok(NextAction::StartProcessing(transaction))
-    } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
-        Ok(NextAction::ReturnSavedResponse(saved_response))
-    }
+// Keys first used before this point in time have expired.
+pub(super) fn expiry_cutoff(settings: &IdempotencySettings) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::hours(settings.ttl_hours as i64)
 }
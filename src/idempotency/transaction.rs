@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use actix_web::{HttpMessage, HttpRequest};
+use sqlx::{PgPool, Postgres, Transaction};
+
+// The transaction `handle_idempotency_key` claimed the key in, lent to the handler
+// through the request extensions for as long as it runs.
+#[derive(Clone)]
+pub(super) struct ClaimTransaction(Rc<RefCell<Option<Transaction<'static, Postgres>>>>);
+
+impl ClaimTransaction {
+    pub(super) fn new(transaction: Transaction<'static, Postgres>) -> Self {
+        Self(Rc::new(RefCell::new(Some(transaction))))
+    }
+
+    pub(super) fn take(&self) -> Option<Transaction<'static, Postgres>> {
+        self.0.borrow_mut().take()
+    }
+}
+
+/// The transaction a handler stores the effects of a request in.
+///
+/// Behind `handle_idempotency_key`, it is the transaction the key was claimed in: the
+/// effects of the request are committed along with its saved response, and the
+/// request holds a single connection. Anywhere else, it is a transaction of its own.
+/// Dropping it without committing rolls back the claim too, which releases the key.
+pub struct RequestTransaction {
+    transaction: Transaction<'static, Postgres>,
+    // Where to hand the transaction back to, if it was lent by the middleware.
+    lender: Option<ClaimTransaction>,
+}
+
+impl RequestTransaction {
+    pub async fn begin(request: &HttpRequest, pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let lender = request.extensions().get::<ClaimTransaction>().cloned();
+        if let Some(lender) = lender {
+            if let Some(transaction) = lender.take() {
+                return Ok(Self { transaction, lender: Some(lender) });
+            }
+        }
+        Ok(Self { transaction: pool.begin().await?, lender: None })
+    }
+
+    /// Commit the effects of the request, or hand them back to the middleware, which
+    /// commits them with the response.
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        match self.lender {
+            Some(lender) => {
+                lender.0.replace(Some(self.transaction));
+                Ok(())
+            }
+            None => self.transaction.commit().await,
+        }
+    }
+}
+
+impl Deref for RequestTransaction {
+    type Target = Transaction<'static, Postgres>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.transaction
+    }
+}
+
+impl DerefMut for RequestTransaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.transaction
+    }
+}
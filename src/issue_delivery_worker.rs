@@ -1,5 +1,7 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
 use sqlx::{PgPool, Postgres, Transaction};
 use tracing::field::display;
 use tracing::Span;
@@ -8,7 +10,9 @@ use uuid::Uuid;
 use crate::configuration::Settings;
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
+use crate::metrics::ISSUE_DELIVERIES_TOTAL;
 use crate::startup::get_connection_pool;
+use crate::telemetry::link_to_trace_context;
 
 pub enum ExecutionOutcome {
     TaskCompleted,
@@ -32,24 +36,31 @@ pub async fn try_execute_task(
         return Ok(ExecutionOutcome::EmptyQueue);
     }
 
-    let (transaction, issue_id, email) = task.unwrap();
+    let (transaction, DeliveryTask { issue_id, email, trace_context }) = task.unwrap();
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
+        .record("newsletter_issue_id", display(issue_id))
+        .record("subscriber_email", display(&email));
+    if let Some(trace_context) = trace_context {
+        link_to_trace_context(&Span::current(), &trace_context);
+    }
 
-    match SubscriberEmail::parse(email.clone()) {
+    let outcome = match SubscriberEmail::parse(email.clone()) {
         Ok(email) => {
             let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
+            match email_client
                 .send_email(&email, &issue.title, &issue.html_content, &issue.text_content)
                 .await
             {
-                tracing::error!(
-                error.cause_chain = ?e,
-                error.message = %e,
-                "Failed to deliver issue to a confirmed subscriber. \
-                Skipping.",
-                );
+                Ok(()) => "sent",
+                Err(e) => {
+                    tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to deliver issue to a confirmed subscriber. \
+                    Skipping.",
+                    );
+                    "failed"
+                }
             }
         }
         Err(e) => {
@@ -59,8 +70,10 @@ pub async fn try_execute_task(
             "Skipping a confirmed subscriber. \
             Their stored contact details are invalid",
             );
+            "invalid_email"
         }
-    }
+    };
+    ISSUE_DELIVERIES_TOTAL.with_label_values(&[outcome]).inc();
 
     delete_task(transaction, issue_id, &email).await?;
     Ok(ExecutionOutcome::TaskCompleted)
@@ -68,15 +81,25 @@ pub async fn try_execute_task(
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct DeliveryTask {
+    issue_id: Uuid,
+    email: String,
+    trace_context: Option<String>,
+}
+
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+) -> Result<Option<(PgTransaction, DeliveryTask)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
 
-    let r = sqlx::query!(
+    let task = sqlx::query_as!(
+        DeliveryTask,
         r#"
-            SELECT newsletter_issue_id, subscriber_email
+            SELECT
+                newsletter_issue_id AS issue_id,
+                subscriber_email AS email,
+                trace_context
             FROM issue_delivery_queue
             FOR UPDATE
             SKIP LOCKED
@@ -85,11 +108,7 @@ async fn dequeue_task(
     )
     .fetch_optional(&mut *transaction)
     .await?;
-    if let Some(r) = r {
-        Ok(Some((transaction, r.newsletter_issue_id, r.subscriber_email)))
-    } else {
-        Ok(None)
-    }
+    Ok(task.map(|task| (transaction, task)))
 }
 
 #[tracing::instrument(skip_all)]
@@ -137,8 +156,119 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
+pub struct QueuedDelivery {
+    pub issue_id: Uuid,
+    pub email: String,
+    pub enqueued_at: DateTime<Utc>,
+}
+
+/// The deliveries still waiting in the queue, oldest first.
+#[tracing::instrument(skip_all)]
+pub async fn list_queued_deliveries(pool: &PgPool) -> Result<Vec<QueuedDelivery>, anyhow::Error> {
+    let deliveries = sqlx::query_as!(
+        QueuedDelivery,
+        r#"
+        SELECT
+            newsletter_issue_id AS issue_id,
+            subscriber_email AS email,
+            enqueued_at
+        FROM issue_delivery_queue
+        ORDER BY enqueued_at, newsletter_issue_id, subscriber_email
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries)
+}
+
+/// Queue an issue again for every confirmed subscriber, or only for `email`.
+///
+/// Deliveries that are still queued are left alone. Returns how many were added.
+#[tracing::instrument(skip(pool))]
+pub async fn requeue_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+    email: Option<&str>,
+) -> Result<u64, anyhow::Error> {
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed' AND ($2::TEXT IS NULL OR email = $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        email,
+    )
+    .execute(pool)
+    .await?;
+    Ok(outcome.rows_affected())
+}
+
+const WORKER_NAME: &str = "issue_delivery";
+// The name the worker of this process records its heartbeats under. Every process
+// has its own, so that one worker cannot vouch for another that has died.
+static WORKER_INSTANCE: Lazy<String> =
+    Lazy::new(|| format!("{}-{}-{}", WORKER_NAME, std::process::id(), Uuid::new_v4()));
+// How often a busy worker records a heartbeat. An idle one records one on every poll.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+// Heartbeats of processes that have been gone this long are deleted.
+const HEARTBEAT_RETENTION_HOURS: i32 = 24;
+
+pub fn worker_instance() -> &'static str {
+    &WORKER_INSTANCE
+}
+
+/// Record that the worker of this process is alive.
+#[tracing::instrument(skip_all, err)]
+pub async fn record_heartbeat(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_name, last_seen_at)
+        VALUES ($1, now())
+        ON CONFLICT (worker_name) DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at
+        "#,
+        worker_instance(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// When the worker of this process last recorded a heartbeat, if ever.
+#[tracing::instrument(skip_all)]
+pub async fn last_heartbeat(pool: &PgPool) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT last_seen_at FROM worker_heartbeats WHERE worker_name = $1",
+        worker_instance(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.last_seen_at))
+}
+
+// Processes that have stopped leave their heartbeat behind: clear out the old ones.
+#[tracing::instrument(skip_all, err)]
+async fn delete_stale_heartbeats(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let outcome = sqlx::query!(
+        "DELETE FROM worker_heartbeats WHERE last_seen_at < now() - make_interval(hours => $1)",
+        HEARTBEAT_RETENTION_HOURS,
+    )
+    .execute(pool)
+    .await?;
+    Ok(outcome.rows_affected())
+}
+
 async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+    let mut last_heartbeat: Option<Instant> = None;
     loop {
+        if last_heartbeat.is_none_or(|at| at.elapsed() >= HEARTBEAT_INTERVAL) {
+            // A failure is logged; the worker keeps going and tries again next time.
+            if record_heartbeat(&pool).await.is_ok() {
+                last_heartbeat = Some(Instant::now());
+            }
+        }
         match try_execute_task(&pool, &email_client).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
@@ -154,5 +284,7 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
+    // A failure is logged: stale heartbeats do no harm until the next start.
+    let _ = delete_stale_heartbeats(&connection_pool).await;
     worker_loop(connection_pool, email_client).await
 }
@@ -1,12 +1,20 @@
 // Purpose: Main library file for the application.
 pub mod authentication;
 pub mod configuration;
+pub mod cookie_keys;
+pub mod csrf;
 pub mod domain;
 pub mod email_client;
+pub mod i18n;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod metrics;
+pub mod migrations;
 pub mod routes;
+pub mod security_headers;
 pub mod session_state;
 pub mod startup;
+pub mod subscription_throttling;
 pub mod telemetry;
+pub mod templates;
 pub mod utils;
@@ -1,25 +1,78 @@
 use std::fmt::{Debug, Display};
 
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use futures_util::future::{select_all, BoxFuture};
+use futures_util::FutureExt;
 use robust_rust::configuration::get_configuration;
+use robust_rust::idempotency::run_cleanup_until_stopped;
 use robust_rust::issue_delivery_worker::run_worker_until_stopped;
-use robust_rust::startup::Application;
+use robust_rust::migrations::prepare_database;
+use robust_rust::startup::{Application, RunMode};
 use robust_rust::telemetry::{get_subscriber, init_subscriber};
 use tokio::task::JoinError;
 
+#[derive(Parser)]
+#[command(name = "robust-rust")]
+struct Cli {
+    /// What to run. Without a mode, the process runs both the API and the background work.
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+enum Mode {
+    /// Only serve HTTP requests.
+    Api,
+    /// Only run the background work: issue deliveries and idempotency key cleanup.
+    /// The health checks and the metrics are still served over HTTP.
+    Worker,
+}
+
+impl Cli {
+    fn run_mode(&self) -> RunMode {
+        match self.mode {
+            None => RunMode::All,
+            Some(Mode::Api) => RunMode::Api,
+            Some(Mode::Worker) => RunMode::Worker,
+        }
+    }
+}
+
+type Task = BoxFuture<'static, Result<Result<(), anyhow::Error>, JoinError>>;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("robust_rust".into(), "info".into(), std::io::stdout);
+    let run_mode = Cli::parse().run_mode();
+    let configuration = get_configuration().context("Failed to read configuration.")?;
+    let subscriber = get_subscriber(
+        "robust_rust".into(),
+        "info".into(),
+        std::io::stdout,
+        configuration.otlp.as_ref(),
+    )?;
     init_subscriber(subscriber);
-    let configuration = get_configuration().expect("Failed to read configuration.");
-    let application = Application::build(configuration.clone()).await?;
-    let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    prepare_database(&configuration.database).await?;
+
+    let mut tasks: Vec<(&str, Task)> = Vec::new();
+    let application = Application::build(configuration.clone(), run_mode).await?;
+    let application_task = tokio::spawn(async move { Ok(application.run_until_stopped().await?) });
+    let server_name = if run_mode.serves_api() { "API" } else { "Health and metrics server" };
+    tasks.push((server_name, application_task.boxed()));
+    if run_mode.runs_worker() {
+        let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+        let cleanup_task = tokio::spawn(run_cleanup_until_stopped(configuration));
+        tasks.push(("Background worker", worker_task.boxed()));
+        tasks.push(("Idempotency cleanup", cleanup_task.boxed()));
+    }
 
-    tokio::select! {
-        o = application_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
-    };
+    // The process exits as soon as any of its tasks does.
+    let (task_names, tasks): (Vec<_>, Vec<_>) = tasks.into_iter().unzip();
+    let (outcome, index, _) = select_all(tasks).await;
+    report_exit(task_names[index], outcome);
 
+    // Flush the spans that have not been exported yet.
+    opentelemetry::global::shutdown_tracer_provider();
     Ok(())
 }
 
@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web_lab::middleware::Next;
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Histogram, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+// Purpose: Prometheus metrics shared by the API and the issue delivery worker.
+// Everything lives in the default registry, which `/metrics` exposes.
+
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests served, by route and status code.",
+        &["method", "route", "status"]
+    )
+    .expect("Failed to register http_requests_total")
+});
+
+pub static HTTP_REQUEST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time spent serving HTTP requests, by route.",
+        &["method", "route"]
+    )
+    .expect("Failed to register http_request_duration_seconds")
+});
+
+pub static PASSWORD_VERIFICATION_DURATION_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "password_verification_duration_seconds",
+        "Time spent verifying a password candidate against its Argon2 hash."
+    )
+    .expect("Failed to register password_verification_duration_seconds")
+});
+
+pub static ISSUE_DELIVERIES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "issue_deliveries_total",
+        "Newsletter issue deliveries attempted by the worker, by outcome.",
+        &["outcome"]
+    )
+    .expect("Failed to register issue_deliveries_total")
+});
+
+pub static ISSUE_DELIVERY_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("issue_delivery_queue_depth", "Deliveries waiting in the queue.")
+        .expect("Failed to register issue_delivery_queue_depth")
+});
+
+pub static ISSUE_DELIVERY_QUEUE_OLDEST_TASK_AGE_SECONDS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "issue_delivery_queue_oldest_task_age_seconds",
+        "How long the oldest delivery in the queue has been waiting, 0 if it is empty."
+    )
+    .expect("Failed to register issue_delivery_queue_oldest_task_age_seconds")
+});
+
+pub static DB_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "db_pool_connections",
+        "Connections of the API database pool, by state (idle, in_use or max).",
+        &["state"]
+    )
+    .expect("Failed to register db_pool_connections")
+});
+
+// Counts and times every request. Requests are labelled with the route pattern they
+// matched rather than their path, to keep the number of series bounded.
+pub async fn record_http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = Instant::now();
+    // Patterns come from the resource map of the whole app: they can be looked up
+    // before routing has happened.
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".into());
+    let method = req.method().clone();
+    let outcome = next.call(req).await;
+
+    let method = method.as_str();
+    let status = match &outcome {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    HTTP_REQUESTS_TOTAL.with_label_values(&[method, &route, status.as_str()]).inc();
+    HTTP_REQUEST_DURATION_SECONDS
+        .with_label_values(&[method, &route])
+        .observe(start.elapsed().as_secs_f64());
+    outcome
+}
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{Connection, PgConnection};
+
+use crate::configuration::DatabaseSettings;
+
+/// The migrations in `migrations/`, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Make sure the database schema is one this binary can work with, applying the
+/// embedded migrations first if `migrate_on_startup` is set.
+///
+/// Instances starting together take turns through a Postgres advisory lock - the one
+/// `sqlx migrate run` takes too. The check fails if the database has migrations that
+/// the binary does not know about, i.e. if a newer release has already been deployed.
+#[tracing::instrument(skip_all, err)]
+pub async fn prepare_database(settings: &DatabaseSettings) -> Result<(), anyhow::Error> {
+    let mut connection = PgConnection::connect_with(&settings.with_db())
+        .await
+        .context("Failed to connect to Postgres.")?;
+    connection.lock().await.context("Failed to take the migration lock.")?;
+    let outcome = check_and_migrate(&mut connection, settings.migrate_on_startup).await;
+    connection.unlock().await.context("Failed to release the migration lock.")?;
+    outcome
+}
+
+async fn check_and_migrate(
+    connection: &mut PgConnection,
+    migrate: bool,
+) -> Result<(), anyhow::Error> {
+    let has_migrations_table: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&mut *connection)
+            .await
+            .context("Failed to look for the migrations table.")?;
+    let applied: HashSet<i64> = if has_migrations_table {
+        connection
+            .list_applied_migrations()
+            .await
+            .context("Failed to list the applied migrations.")?
+            .into_iter()
+            .map(|m| m.version)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let known: HashSet<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    let mut unknown: Vec<_> = applied.difference(&known).collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        anyhow::bail!(
+            "The database schema is newer than this binary: migrations {:?} are unknown to it.",
+            unknown
+        );
+    }
+
+    if migrate {
+        MIGRATOR.run_direct(connection).await.context("Failed to migrate the database.")?;
+    } else if let Some(pending) = known.difference(&applied).min() {
+        tracing::warn!(
+            "The database schema is out of date, starting at migration {}. Run the migrations or \
+             set `database.migrate_on_startup`.",
+            pending
+        );
+    }
+    Ok(())
+}
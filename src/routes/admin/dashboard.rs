@@ -1,15 +1,30 @@
-use actix_web::http::header::ContentType;
 use actix_web::{web, HttpResponse};
 use anyhow::Context;
+use askama::Template;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::csrf::CsrfToken;
 use crate::session_state::TypedSession;
+use crate::templates::render_page;
 use crate::utils::e500;
 
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct DashboardPage<'a> {
+    username: String,
+    csrf_token: &'a str,
+}
+
+#[utoipa::path(
+    get, path = "/admin/dashboard", tag = "admin",
+    security(("session" = [])),
+    responses((status = 200, description = "The admin dashboard", content_type = "text/html"))
+)]
 pub async fn admin_dashboard(
     session: TypedSession,
     pool: web::Data<PgPool>,
+    csrf_token: web::ReqData<CsrfToken>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
         get_username(user_id, &pool).await.map_err(e500)?
@@ -17,28 +32,7 @@ pub async fn admin_dashboard(
         return Ok(HttpResponse::SeeOther().insert_header(("location", "/login")).finish());
     };
 
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
-        r#"<!DOCTYPE html>
-            <html lang="en">
-            <head>
-            <meta http-equiv="content-type" content="text/html; charset=utf-8">
-            <title>Admin dashboard</title>
-            </head>
-            <body>
-            <p>Welcome {username}!</p>
-            <p>Available actions:</p>
-            <ol>
-            <li><a href="/admin/password">Change password</a></li>
-            <li><a href="/admin/newsletters">Send Newsletter</a></li>
-            <li>
-                <form name="logoutForm" action="/admin/logout" method="post">
-                    <input type="submit" value="Logout">
-                </form>
-            </li>
-            </ol>
-            </body>
-            </html>"#,
-    )))
+    render_page(&DashboardPage { username, csrf_token: csrf_token.as_str() })
 }
 
 #[tracing::instrument(name = "Get username", skip(pool))]
@@ -1,15 +1,27 @@
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 
+use crate::authentication::{SessionIndex, UserId};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        Ok(see_other("/login"))
-    } else {
-        session.log_out();
-        FlashMessage::info("You have successfully logged out.").send();
-        Ok(see_other("/login"))
+#[utoipa::path(
+    post, path = "/admin/logout", tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 303, description = "Redirects to the login form"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
+pub async fn log_out(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    session_index: web::Data<SessionIndex>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        session_index.revoke(**user_id, session_id).await.map_err(e500)?;
     }
+    session.log_out();
+    FlashMessage::info("You have successfully logged out.").send();
+    Ok(see_other("/login"))
 }
@@ -2,8 +2,12 @@ mod dashboard;
 mod logout;
 mod newsletters;
 mod password;
+mod sessions;
+mod tokens;
 
-pub use dashboard::admin_dashboard;
-pub use logout::log_out;
+pub use dashboard::{__path_admin_dashboard, admin_dashboard};
+pub use logout::{__path_log_out, log_out};
 pub use newsletters::*;
 pub use password::*;
+pub use sessions::*;
+pub use tokens::*;
@@ -1,58 +1,30 @@
-use std::fmt::Write;
-
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::csrf::CsrfToken;
+use crate::templates::{flash_messages, render_page};
+
+#[derive(Template)]
+#[template(path = "admin/publish_newsletter.html")]
+struct PublishNewsletterPage<'a> {
+    flash_messages: Vec<String>,
+    idempotency_key: String,
+    csrf_token: &'a str,
+}
 
+#[utoipa::path(
+    get, path = "/admin/newsletters", tag = "admin",
+    security(("session" = [])),
+    responses((status = 200, description = "The newsletter form", content_type = "text/html"))
+)]
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let mut incoming_flash = String::new();
-    for message in flash_messages.iter() {
-        writeln!(incoming_flash, "<p><i>{}</i></p>", message.content()).unwrap();
-    }
-    let idempotency_key = uuid::Uuid::new_v4().to_string();
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
-        r#"<!DOCTYPE html>
-                <html lang="en">
-                <head>
-                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                    <title>Publish Newsletter Issue</title>
-                </head>
-                <body>
-                    {incoming_flash}
-                    <form action="/admin/newsletters" method="post">
-                        <label>Title:<br>
-                            <input
-                                type="text"
-                                placeholder="Enter the issue title"
-                                name="title"
-                            >
-                        </label>
-                        <br>
-                        <label>Plain text content:<br>
-                            <textarea
-                                placeholder="Enter the content in plain text"
-                                name="text_content"
-                                rows="20"
-                                cols="50"
-                            ></textarea>
-                        </label>
-                        <br>
-                        <label>HTML content:<br>
-                            <textarea
-                                placeholder="Enter the content in HTML format"
-                                name="html_content"
-                                rows="20"
-                                cols="50"
-                            ></textarea>
-                        </label>
-                        <br>
-                        <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
-                        <button type="submit">Publish</button>
-                    </form>
-                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-                </body>
-                </html>"#,
-    )))
+    render_page(&PublishNewsletterPage {
+        flash_messages: self::flash_messages(&flash_messages),
+        idempotency_key: uuid::Uuid::new_v4().to_string(),
+        csrf_token: csrf_token.as_str(),
+    })
 }
@@ -1,51 +1,59 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::web::ReqData;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::middleware::Next;
 use anyhow::Context;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::authentication::UserId;
-use crate::idempotency::{
-    get_saved_response, save_response, try_processing, IdempotencyKey, NextAction,
-};
-use crate::utils::{e400, e500, see_other};
+use crate::idempotency::{RequestTransaction, IDEMPOTENT_REPLAYED};
+use crate::telemetry::current_trace_context;
+use crate::utils::{e500, see_other};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize, utoipa::ToSchema)]
 pub struct NewsletterContent {
     title: String,
     text_content: String,
     html_content: String,
-    idempotency_key: String,
 }
 
+// Retries are deduplicated by `handle_idempotency_key`, which takes the key of the
+// form from its `idempotency_key` field.
 #[tracing::instrument(
 name = "Publish a newsletter issue", 
     skip_all,
     fields(user_id = %*user_id)
 )]
+#[utoipa::path(
+    post, path = "/admin/newsletters", tag = "admin",
+    security(("session" = []), ("bearer" = ["newsletters:publish"])),
+    params((
+        "Idempotency-Key" = Option<String>, Header,
+        description = "Makes the request safe to retry: later requests with the same key get the first response. Forms can send it as the `idempotency_key` field instead"
+    )),
+    request_body(content = NewsletterContent, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "The issue has been accepted for delivery"),
+        (status = 400, description = "The idempotency key is invalid"),
+        (status = 403, description = "The CSRF token is missing or wrong, for requests authenticated with a session"),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key has already been used for a different request"),
+    )
+)]
 pub async fn publish_newsletter(
     form: web::Form<NewsletterContent>,
     pool: web::Data<PgPool>,
     user_id: ReqData<UserId>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let user_id = user_id.into_inner();
-    let NewsletterContent { title, text_content, html_content, idempotency_key } = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let mut transaction =
-        match try_processing(&pool, &idempotency_key, *user_id).await.map_err(e500)? {
-            NextAction::StartProcessing(t) => t,
-            NextAction::ReturnSavedResponse(saved_response) => {
-                success_message().send();
-                return Ok(saved_response);
-            }
-        };
-
-    if let Some(save_response) =
-        get_saved_response(&pool, &idempotency_key, *user_id).await.map_err(e500)?
-    {
-        return Ok(save_response);
-    }
+    let NewsletterContent { title, text_content, html_content } = form.0;
+    let mut transaction = RequestTransaction::begin(&request, &pool)
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
     let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
         .await
         .context("Failed to store newsletter issue details")
@@ -54,11 +62,22 @@ pub async fn publish_newsletter(
         .await
         .context("Failed to enqueue delivery task")
         .map_err(e500)?;
+    transaction.commit().await.context("Failed to commit SQL transaction").map_err(e500)?;
 
-    let response = see_other("/admin/newsletters");
-    let response =
-        save_response(transaction, &idempotency_key, *user_id, response).await.map_err(e500)?;
     success_message().send();
+    Ok(see_other("/admin/newsletters"))
+}
+
+// Flash messages are not part of the stored response: a replayed submission gets the
+// same confirmation as the original one. Wrap it outside `handle_idempotency_key`.
+pub async fn confirm_replayed_publication(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let response = next.call(req).await?;
+    if response.headers().contains_key(IDEMPOTENT_REPLAYED) && response.status().is_redirection() {
+        success_message().send();
+    }
     Ok(response)
 }
 
@@ -67,7 +86,7 @@ fn success_message() -> FlashMessage {
 }
 
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     text_content: &str,
@@ -96,7 +115,7 @@ async fn insert_newsletter_issue(
 }
 
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+pub async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
 ) -> Result<(), sqlx::Error> {
@@ -104,13 +123,16 @@ async fn enqueue_delivery_tasks(
         r#"
             INSERT INTO issue_delivery_queue (
                 newsletter_issue_id,
-                subscriber_email
+                subscriber_email,
+                trace_context
             )
-            SELECT $1, email
+            SELECT $1, email, $2
             FROM subscriptions
             WHERE status = 'confirmed'
         "#,
         newsletter_issue_id,
+        // Deliveries link back to the request that published the issue.
+        current_trace_context(),
     )
     .execute(&mut *(*transaction))
     .await?;
@@ -1,63 +1,35 @@
-use std::fmt::Write;
-
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
 
+use crate::csrf::CsrfToken;
 use crate::session_state::TypedSession;
+use crate::templates::{flash_messages, render_page};
 use crate::utils::{e500, see_other};
 
+#[derive(Template)]
+#[template(path = "admin/change_password.html")]
+struct ChangePasswordPage<'a> {
+    flash_messages: Vec<String>,
+    csrf_token: &'a str,
+}
+
+#[utoipa::path(
+    get, path = "/admin/password", tag = "admin",
+    security(("session" = [])),
+    responses((status = 200, description = "The change password form", content_type = "text/html"))
+)]
 pub async fn change_password_form(
     session: TypedSession,
     flash_message: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
 ) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
     };
 
-    let mut msg_html = String::new();
-    for m in flash_message.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
-        r#"<!DOCTYPE html>
-            <html lang="en">
-                <head>
-                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                    <title>Change Password</title>
-                </head>
-                <body>
-                    {msg_html}
-                    <form action="/admin/password" method="post">
-                        <label>Current password
-                        <input
-                        type="password"
-                        placeholder="Enter current password"
-                        name="current_password"
-                        >
-                        </label>
-                        <br>
-                        <label>New password
-                        <input
-                        type="password"
-                        placeholder="Enter new password"
-                        name="new_password"
-                        >
-                        </label>
-                        <br>
-                        <label>Confirm new password
-                        <input
-                        type="password"
-                        placeholder="Type the new password again"
-                        name="new_password_check"
-                        >
-                        </label>
-                        <br>
-                        <button type="submit">Change password</button>
-                    </form>
-                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-                </body>
-            </html>"#,
-    )))
+    render_page(&ChangePasswordPage {
+        flash_messages: flash_messages(&flash_message),
+        csrf_token: csrf_token.as_str(),
+    })
 }
@@ -1,23 +1,44 @@
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
-use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
+use crate::authentication::{
+    validate_credentials, AuthError, Credentials, PasswordPolicy, SessionIndex, UserId,
+};
+use crate::configuration::PasswordHashingSettings;
 use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct FormData {
+    #[schema(value_type = String, format = Password)]
     current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password_check: Secret<String>,
 }
 
+#[utoipa::path(
+    post, path = "/admin/password", tag = "admin",
+    security(("session" = [])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirects to the change password form"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    password_policy: web::Data<PasswordPolicy>,
+    hashing: web::Data<PasswordHashingSettings>,
+    session: TypedSession,
+    session_index: web::Data<SessionIndex>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
@@ -31,8 +52,9 @@ pub async fn change_password(
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
 
-    let credentials = Credentials { username, password: form.0.current_password };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    let credentials =
+        Credentials { username: username.clone(), password: form.0.current_password.clone() };
+    if let Err(e) = validate_credentials(credentials, &hashing, &pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -42,9 +64,32 @@ pub async fn change_password(
         };
     };
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    if let Err(violation) = password_policy.check(
+        &form.new_password,
+        Some(&form.current_password),
+        &[username.as_str()],
+    ) {
+        FlashMessage::error(violation.to_string()).send();
+        return Ok(see_other("/admin/password"));
+    }
+
+    let mut transaction = pool
+        .begin()
         .await
+        .context("Failed to acquire a Postgres connection from the pool")
         .map_err(e500)?;
+    crate::authentication::change_password(
+        *user_id,
+        form.0.new_password,
+        &hashing,
+        &mut transaction,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.context("Failed to commit SQL transaction").map_err(e500)?;
+    // Whoever may have learnt the old password is logged out of every other device.
+    let current_session = session.get_session_id().map_err(e500)?;
+    session_index.revoke_all(*user_id, current_session).await.map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,49 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use uuid::Uuid;
+
+use crate::authentication::{SessionIndex, SessionMetadata, UserId};
+use crate::csrf::CsrfToken;
+use crate::session_state::TypedSession;
+use crate::templates::{filters, flash_messages, render_page};
+use crate::utils::e500;
+
+#[derive(Template)]
+#[template(path = "admin/sessions.html")]
+struct SessionsPage<'a> {
+    flash_messages: Vec<String>,
+    csrf_token: &'a str,
+    sessions: Vec<SessionMetadata>,
+    // The session the page is shown in, which is not offered for revocation.
+    current_session: Option<Uuid>,
+}
+
+impl SessionsPage<'_> {
+    fn is_current(&self, session: &SessionMetadata) -> bool {
+        Some(session.session_id) == self.current_session
+    }
+}
+
+#[utoipa::path(
+    get, path = "/admin/sessions", tag = "admin",
+    security(("session" = [])),
+    responses((status = 200, description = "The active sessions of the user", content_type = "text/html"))
+)]
+pub async fn list_sessions(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    session_index: web::Data<SessionIndex>,
+    flash_message: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session = session.get_session_id().map_err(e500)?;
+    let sessions = session_index.list(**user_id).await.map_err(e500)?;
+
+    render_page(&SessionsPage {
+        flash_messages: flash_messages(&flash_message),
+        csrf_token: csrf_token.as_str(),
+        sessions,
+        current_session,
+    })
+}
@@ -0,0 +1,7 @@
+mod get;
+mod post;
+
+pub use get::{__path_list_sessions, list_sessions};
+pub use post::{
+    __path_revoke_all_sessions, __path_revoke_session, revoke_all_sessions, revoke_session,
+};
@@ -0,0 +1,52 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+use crate::authentication::{SessionIndex, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct FormData {
+    session_id: Uuid,
+}
+
+#[tracing::instrument(name = "Revoke a session", skip(form, user_id, session_index))]
+#[utoipa::path(
+    post, path = "/admin/sessions/revoke", tag = "admin",
+    security(("session" = [])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirects to the active sessions"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
+pub async fn revoke_session(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    session_index: web::Data<SessionIndex>,
+) -> Result<HttpResponse, actix_web::Error> {
+    session_index.revoke(**user_id, form.session_id).await.map_err(e500)?;
+    FlashMessage::info("The session has been revoked.").send();
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(name = "Log out everywhere", skip(session, user_id, session_index))]
+#[utoipa::path(
+    post, path = "/admin/sessions/revoke_all", tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 303, description = "Redirects to the login form"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
+pub async fn revoke_all_sessions(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    session_index: web::Data<SessionIndex>,
+) -> Result<HttpResponse, actix_web::Error> {
+    session_index.revoke_all(**user_id, None).await.map_err(e500)?;
+    session.log_out();
+    FlashMessage::info("You have been logged out of every session.").send();
+    Ok(see_other("/login"))
+}
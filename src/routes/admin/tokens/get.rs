@@ -0,0 +1,40 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::authentication::{get_api_tokens, ApiToken, ApiTokenScope, UserId};
+use crate::csrf::CsrfToken;
+use crate::templates::{filters, flash_messages, render_page};
+use crate::utils::e500;
+
+#[derive(Template)]
+#[template(path = "admin/api_tokens.html")]
+struct ApiTokensPage<'a> {
+    flash_messages: Vec<String>,
+    csrf_token: &'a str,
+    tokens: Vec<ApiToken>,
+    // The scopes a new token can be granted.
+    scopes: [ApiTokenScope; 3],
+}
+
+#[utoipa::path(
+    get, path = "/admin/tokens", tag = "admin",
+    security(("session" = [])),
+    responses((status = 200, description = "The API tokens of the user", content_type = "text/html"))
+)]
+pub async fn list_api_tokens(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    flash_message: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tokens = get_api_tokens(**user_id, &pool).await.map_err(e500)?;
+
+    render_page(&ApiTokensPage {
+        flash_messages: flash_messages(&flash_message),
+        csrf_token: csrf_token.as_str(),
+        tokens,
+        scopes: ApiTokenScope::ALL,
+    })
+}
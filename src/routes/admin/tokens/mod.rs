@@ -0,0 +1,7 @@
+mod get;
+mod post;
+
+pub use get::{__path_list_api_tokens, list_api_tokens};
+pub use post::{
+    __path_create_api_token, __path_revoke_api_token, create_api_token, revoke_api_token,
+};
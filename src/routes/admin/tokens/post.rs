@@ -0,0 +1,123 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use askama::Template;
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{generate_api_token, store_api_token, ApiTokenScope, UserId};
+use crate::templates::render_page;
+use crate::utils::{e500, see_other};
+
+#[derive(Default, utoipa::ToSchema)]
+pub struct CreateFormData {
+    name: String,
+    scope: Vec<String>,
+    #[schema(example = "30")]
+    expires_in_days: Option<String>,
+}
+
+// Checkboxes submit one `scope` field per ticked box, which `web::Form` cannot
+// deserialize into a `Vec`: the form is collected from its raw field pairs instead.
+impl FromIterator<(String, String)> for CreateFormData {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(fields: I) -> Self {
+        let mut form = Self::default();
+        for (field, value) in fields {
+            match field.as_str() {
+                "name" => form.name = value,
+                "scope" => form.scope.push(value),
+                "expires_in_days" => form.expires_in_days = Some(value),
+                _ => {}
+            }
+        }
+        form
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/api_token_created.html")]
+struct ApiTokenCreatedPage<'a> {
+    token: &'a str,
+}
+
+#[tracing::instrument(name = "Create an API token", skip(form, pool, user_id))]
+#[utoipa::path(
+    post, path = "/admin/tokens", tag = "admin",
+    security(("session" = [])),
+    request_body(content = inline(CreateFormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Shows the new token, once", content_type = "text/html"),
+        (status = 303, description = "Redirects to the API tokens if the form is invalid"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
+pub async fn create_api_token(
+    form: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let CreateFormData { name, scope, expires_in_days } = form.into_inner().into_iter().collect();
+    let name = name.trim();
+    let mut scopes = Vec::new();
+    for value in scope {
+        match ApiTokenScope::try_from(value.as_str()) {
+            Ok(scope) if !scopes.contains(&scope) => scopes.push(scope),
+            Ok(_) => {}
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other("/admin/tokens"));
+            }
+        }
+    }
+    let expires_at = match expires_in_days.as_deref() {
+        None | Some("never") => None,
+        Some(days) => match days.parse::<u16>() {
+            Ok(days) => Some(Utc::now() + Duration::days(days.into())),
+            Err(_) => {
+                FlashMessage::error("The token expiry is invalid.").send();
+                return Ok(see_other("/admin/tokens"));
+            }
+        },
+    };
+    if name.is_empty() {
+        FlashMessage::error("Please give the token a name.").send();
+        return Ok(see_other("/admin/tokens"));
+    }
+    if scopes.is_empty() {
+        FlashMessage::error("Please grant the token at least one scope.").send();
+        return Ok(see_other("/admin/tokens"));
+    }
+
+    let token = generate_api_token();
+    store_api_token(**user_id, name, &scopes, expires_at, &token, &pool).await.map_err(e500)?;
+
+    // The token is not stored anywhere we can read it back from: this is the only
+    // time it is ever displayed.
+    render_page(&ApiTokenCreatedPage { token: token.expose_secret() })
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct RevokeFormData {
+    token_id: Uuid,
+}
+
+#[tracing::instrument(name = "Revoke an API token", skip(form, pool, user_id))]
+#[utoipa::path(
+    post, path = "/admin/tokens/revoke", tag = "admin",
+    security(("session" = [])),
+    request_body(content = inline(RevokeFormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirects to the API tokens"),
+        (status = 403, description = "The CSRF token is missing or wrong"),
+    )
+)]
+pub async fn revoke_api_token(
+    form: web::Form<RevokeFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    crate::authentication::revoke_api_token(**user_id, form.token_id, &pool).await.map_err(e500)?;
+    FlashMessage::info("The API token has been revoked.").send();
+    Ok(see_other("/admin/tokens"))
+}
@@ -0,0 +1,112 @@
+use actix_web::error::JsonPayloadError;
+use actix_web::http::header::{HeaderName, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+
+use crate::authentication::ApiTokenScope;
+use crate::routes::{error_chain_fmt, ConfirmationError, LoginError, SubscribeError};
+
+// Purpose: The error type of the `/api/v1` endpoints. Errors are rendered as
+// `{"error": {"code": "...", "message": "..."}}` instead of an HTML page or a redirect.
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error(transparent)]
+    Subscribe(#[from] SubscribeError),
+    #[error(transparent)]
+    Confirmation(#[from] ConfirmationError),
+    #[error(transparent)]
+    Login(#[from] LoginError),
+    #[error("The API token lacks the {0} scope.")]
+    InsufficientScope(ApiTokenScope),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorDetail {
+    #[schema(example = "validation_error")]
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    // A stable, machine readable identifier for the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Subscribe(SubscribeError::ValidationError(_)) => "validation_error",
+            Self::Subscribe(SubscribeError::TooManyRequests(_)) => "too_many_requests",
+            Self::Confirmation(ConfirmationError::InvalidToken) => "invalid_subscription_token",
+            Self::Login(LoginError::AuthError(_)) => "authentication_failed",
+            Self::Login(LoginError::TooManyAttempts(_)) => "too_many_attempts",
+            Self::InsufficientScope(_) => "insufficient_scope",
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::NotFound(_) => "not_found",
+            Self::Subscribe(SubscribeError::UnexpectedError(_))
+            | Self::Confirmation(ConfirmationError::UnexpectedError(_))
+            | Self::Login(LoginError::UnexpectedError(_))
+            | Self::UnexpectedError(_) => "internal_error",
+        }
+    }
+
+    // The `Retry-After` header to send along with the error, if any.
+    fn retry_after(&self) -> Option<(HeaderName, String)> {
+        match self {
+            Self::Subscribe(e) => e.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Subscribe(e) => e.status_code(),
+            Self::Confirmation(e) => e.status_code(),
+            Self::Login(LoginError::AuthError(_)) => StatusCode::UNAUTHORIZED,
+            Self::Login(LoginError::TooManyAttempts(_)) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Login(LoginError::UnexpectedError(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status_code = self.status_code();
+        // The details of unexpected errors end up in the logs, not in front of the caller.
+        let message = if status_code.is_server_error() {
+            "Something went wrong".to_string()
+        } else {
+            self.to_string()
+        };
+        let mut response = HttpResponse::build(status_code);
+        if status_code == StatusCode::UNAUTHORIZED && matches!(self, Self::Login(_)) {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let Some(retry_after) = self.retry_after() {
+            response.insert_header(retry_after);
+        }
+        response.json(ErrorBody { error: ErrorDetail { code: self.code(), message } })
+    }
+}
+
+// Reports malformed JSON bodies in the same shape as every other API error.
+pub fn json_error_handler(e: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::InvalidRequest(e.to_string()).into()
+}
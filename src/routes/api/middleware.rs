@@ -0,0 +1,55 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use sqlx::PgPool;
+
+use super::ApiError;
+use crate::authentication::{authenticate_api_token, bearer_token, ApiTokenScope, UserId};
+use crate::routes::LoginError;
+
+// The `/api/v1` counterpart of `reject_anonymous_users`: only API tokens are accepted,
+// and failures are reported as JSON rather than redirects to the login form.
+pub async fn require_api_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = bearer_token(req.headers())
+        .map_err(LoginError::AuthError)
+        .map_err(ApiError::Login)?
+        .ok_or_else(|| {
+            ApiError::Login(LoginError::AuthError(anyhow::anyhow!(
+                "The request does not carry a bearer token"
+            )))
+        })?;
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| ApiError::UnexpectedError(anyhow::anyhow!("Missing PgPool")))?;
+    let grant = authenticate_api_token(&token, pool)
+        .await
+        .map_err(ApiError::UnexpectedError)?
+        .ok_or_else(|| {
+            ApiError::Login(LoginError::AuthError(anyhow::anyhow!(
+                "The API token is unknown, revoked or expired"
+            )))
+        })?;
+
+    let route = req.match_pattern().unwrap_or_else(|| req.path().to_owned());
+    match ApiTokenScope::required_for(req.method(), &route) {
+        Some(scope) if grant.scopes.contains(&scope) => {}
+        Some(scope) => return Err(ApiError::InsufficientScope(scope).into()),
+        // Every token protected endpoint must declare its scope: fail closed if one does not.
+        None => {
+            return Err(ApiError::UnexpectedError(anyhow::anyhow!(
+                "No API token scope is defined for {} {}",
+                req.method(),
+                route
+            ))
+            .into())
+        }
+    }
+
+    req.extensions_mut().insert(UserId::from(grant.user_id));
+    next.call(req).await
+}
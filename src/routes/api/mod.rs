@@ -0,0 +1,16 @@
+mod errors;
+mod middleware;
+mod newsletters;
+mod openapi;
+mod subscribers;
+mod subscriptions;
+
+pub use errors::{json_error_handler, ApiError, ErrorBody, ErrorDetail};
+pub use middleware::require_api_token;
+pub use newsletters::{
+    __path_api_get_newsletter_issue, __path_api_publish_newsletter, api_get_newsletter_issue,
+    api_publish_newsletter,
+};
+pub use openapi::{__path_api_explorer, __path_openapi_json, api_explorer, openapi_json, ApiDoc};
+pub use subscribers::{__path_api_list_subscribers, api_list_subscribers};
+pub use subscriptions::{__path_api_confirm, __path_api_subscribe, api_confirm, api_subscribe};
@@ -0,0 +1,127 @@
+use actix_web::http::header::LOCATION;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ApiError;
+use crate::authentication::UserId;
+use crate::idempotency::RequestTransaction;
+use crate::routes::{enqueue_delivery_tasks, insert_newsletter_issue};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct PublishedIssue {
+    issue_id: Uuid,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct NewsletterIssueStatus {
+    issue_id: Uuid,
+    title: String,
+    published_at: String,
+    pending_deliveries: i64,
+    #[schema(example = "delivering")]
+    status: &'static str,
+}
+
+#[utoipa::path(
+    post, path = "/api/v1/newsletters", tag = "newsletters",
+    security(("bearer" = ["newsletters:publish"])),
+    params((
+        "Idempotency-Key" = Option<String>, Header,
+        description = "Makes the request safe to retry: later requests with the same key get the first response"
+    )),
+    request_body = NewsletterIssue,
+    responses(
+        (status = 202, description = "The issue has been accepted for delivery", body = PublishedIssue),
+        (status = 400, description = "The issue is invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key has already been used for a different request"),
+    )
+)]
+#[tracing::instrument(
+    name = "Publish a newsletter issue through the API",
+    skip_all,
+    fields(user_id = %*user_id)
+)]
+pub async fn api_publish_newsletter(
+    body: web::Json<NewsletterIssue>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let NewsletterIssue { title, text_content, html_content } = body.0;
+    if title.trim().is_empty() {
+        return Err(ApiError::InvalidRequest("The newsletter issue needs a title.".into()));
+    }
+
+    let mut transaction = RequestTransaction::begin(&request, &pool)
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+        .await
+        .context("Failed to store newsletter issue details")?;
+    enqueue_delivery_tasks(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery task")?;
+    transaction.commit().await.context("Failed to commit SQL transaction")?;
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((LOCATION, format!("/api/v1/newsletters/{}", issue_id)))
+        .json(PublishedIssue { issue_id }))
+}
+
+#[utoipa::path(
+    get, path = "/api/v1/newsletters/{issue_id}", tag = "newsletters",
+    security(("bearer" = ["newsletters:read"])),
+    params(("issue_id" = Uuid, Path, description = "The id returned when the issue was published")),
+    responses(
+        (status = 200, description = "The delivery status of the issue", body = NewsletterIssueStatus),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "There is no such issue", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "Get the delivery status of a newsletter issue", skip(pool))]
+pub async fn api_get_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue_id = issue_id.into_inner();
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            title,
+            published_at,
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+            ) AS "pending_deliveries!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to perform a query to retrieve a newsletter issue.")?
+    .ok_or_else(|| ApiError::NotFound("There is no newsletter issue with this id.".into()))?;
+
+    Ok(HttpResponse::Ok().json(NewsletterIssueStatus {
+        issue_id,
+        title: row.title,
+        published_at: row.published_at,
+        pending_deliveries: row.pending_deliveries,
+        status: if row.pending_deliveries > 0 { "delivering" } else { "delivered" },
+    }))
+}
@@ -0,0 +1,127 @@
+use actix_web::http::header::{HeaderValue, CONTENT_SECURITY_POLICY};
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::routes;
+use crate::security_headers::{CspNonce, CSP_REPORT_PATH};
+use crate::templates::render_page;
+use crate::utils::e500;
+
+// Purpose: The OpenAPI 3 contract of the service, generated from the `#[utoipa::path]`
+// annotations on the handlers. Every route registered in `startup::run` must be listed
+// here - `tests/api/openapi.rs` fails otherwise.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "robust-rust", description = "Newsletter delivery service"),
+    paths(
+        routes::health_check,
+        routes::readiness,
+        routes::metrics,
+        routes::csp_report,
+        routes::home,
+        routes::subscribe,
+        routes::confirm,
+        routes::login_form,
+        routes::login,
+        routes::forgot_password_form,
+        routes::request_password_reset,
+        routes::reset_password_form,
+        routes::reset_password,
+        routes::admin_dashboard,
+        routes::change_password_form,
+        routes::change_password,
+        routes::log_out,
+        routes::list_sessions,
+        routes::revoke_session,
+        routes::revoke_all_sessions,
+        routes::list_api_tokens,
+        routes::create_api_token,
+        routes::revoke_api_token,
+        routes::publish_newsletter_form,
+        routes::publish_newsletter,
+        routes::api_subscribe,
+        routes::api_confirm,
+        routes::api_list_subscribers,
+        routes::api_publish_newsletter,
+        routes::api_get_newsletter_issue,
+        openapi_json,
+        api_explorer,
+    ),
+    components(schemas(
+        routes::Readiness,
+        routes::ReadinessComponents,
+        routes::ComponentStatus,
+        routes::WorkerStatus,
+        routes::NewsletterContent,
+        super::newsletters::NewsletterIssue,
+        super::newsletters::PublishedIssue,
+        super::newsletters::NewsletterIssueStatus,
+        super::subscribers::SubscriberList,
+        super::subscribers::Subscriber,
+        super::subscriptions::SubscriptionStatus,
+        super::ErrorBody,
+        super::ErrorDetail,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "subscriptions", description = "Subscribing to the newsletter"),
+        (name = "subscribers", description = "Managing subscribers"),
+        (name = "newsletters", description = "Publishing newsletter issues"),
+        (name = "web", description = "HTML pages and forms"),
+        (name = "admin", description = "HTML pages and forms of the admin area"),
+        (name = "operations", description = "Operating the service"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+    }
+}
+
+#[utoipa::path(
+    get, path = "/api/openapi.json", tag = "operations",
+    responses((status = 200, description = "This document", content_type = "application/json"))
+)]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[derive(Template)]
+#[template(path = "explorer.html")]
+struct ExplorerPage<'a> {
+    nonce: &'a str,
+}
+
+#[utoipa::path(
+    get, path = "/api/docs", tag = "operations",
+    responses((status = 200, description = "An interactive explorer for this document", content_type = "text/html"))
+)]
+pub async fn api_explorer(nonce: web::ReqData<CspNonce>) -> Result<HttpResponse, actix_web::Error> {
+    // Swagger UI is loaded from a CDN, which the policy of our own pages does not allow.
+    let policy = format!(
+        "default-src 'none'; script-src 'nonce-{nonce}' https://unpkg.com; \
+         style-src https://unpkg.com; img-src 'self' data:; connect-src 'self'; \
+         frame-ancestors 'none'; report-uri {report_path}",
+        nonce = nonce.as_str(),
+        report_path = CSP_REPORT_PATH,
+    );
+    let mut response = render_page(&ExplorerPage { nonce: nonce.as_str() })?;
+    response
+        .headers_mut()
+        .insert(CONTENT_SECURITY_POLICY, HeaderValue::from_str(&policy).map_err(e500)?);
+    Ok(response)
+}
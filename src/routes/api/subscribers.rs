@@ -0,0 +1,67 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ApiError;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+pub struct SubscribersQuery {
+    /// Only list subscribers with this status, e.g. `confirmed`.
+    status: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberList {
+    subscribers: Vec<Subscriber>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get, path = "/api/v1/subscribers", tag = "subscribers",
+    security(("bearer" = ["subscribers:read"])),
+    params(SubscribersQuery),
+    responses(
+        (status = 200, description = "The subscribers", body = SubscriberList),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "List subscribers through the API", skip_all)]
+pub async fn api_list_subscribers(
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscribers = get_subscribers(query.status.as_deref(), &pool).await?;
+    Ok(HttpResponse::Ok().json(SubscriberList { subscribers }))
+}
+
+#[tracing::instrument(name = "Get subscribers", skip(pool))]
+async fn get_subscribers(
+    status: Option<&str>,
+    pool: &PgPool,
+) -> Result<Vec<Subscriber>, anyhow::Error> {
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE $1::TEXT IS NULL OR status = $1
+        ORDER BY subscribed_at
+        "#,
+        status,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve subscribers.")?;
+    Ok(subscribers)
+}
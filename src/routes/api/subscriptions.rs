@@ -0,0 +1,81 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use super::ApiError;
+use crate::email_client::EmailClient;
+use crate::routes::subscriptions::{register_subscriber_within_limits, FormData};
+use crate::routes::subscriptions_confirm::{
+    confirm_subscriber, get_subscriber_id_from_token, Parameters,
+};
+use crate::routes::{ConfirmationError, SubscribeError};
+use crate::startup::ApplicationBaseUrl;
+use crate::subscription_throttling::SubscriptionThrottle;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriptionStatus {
+    #[schema(example = "pending_confirmation")]
+    status: &'static str,
+}
+
+#[utoipa::path(
+    post, path = "/api/v1/subscriptions", tag = "subscriptions",
+    params((
+        "Idempotency-Key" = Option<String>, Header,
+        description = "Makes the request safe to retry: later requests with the same key get the first response"
+    )),
+    request_body = inline(FormData),
+    responses(
+        (status = 202, description = "A confirmation email has been sent", body = SubscriptionStatus),
+        (status = 400, description = "The name or the email is invalid", body = ErrorBody),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key has already been used for a different request"),
+        (status = 429, description = "Too many subscription requests from this client or for this address", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "Adding a new subscriber through the API", skip_all)]
+pub async fn api_subscribe(
+    body: web::Json<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    throttle: web::Data<SubscriptionThrottle>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let new_subscriber = body.0.parse(&request).map_err(SubscribeError::ValidationError)?;
+    register_subscriber_within_limits(
+        new_subscriber,
+        &throttle,
+        &request,
+        &pool,
+        &email_client,
+        &base_url.0,
+    )
+    .await?;
+    Ok(HttpResponse::Accepted().json(SubscriptionStatus { status: "pending_confirmation" }))
+}
+
+#[utoipa::path(
+    post, path = "/api/v1/subscriptions/confirm", tag = "subscriptions",
+    request_body = inline(Parameters),
+    responses(
+        (status = 200, description = "The subscription has been confirmed", body = SubscriptionStatus),
+        (status = 401, description = "The subscription token is unknown", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "Confirm a pending subscriber through the API", skip_all)]
+pub async fn api_confirm(
+    body: web::Json<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = get_subscriber_id_from_token(&body.subscription_token, &pool)
+        .await
+        .context("Failed to retrieve the subscriber associated with the provided token.")
+        .map_err(ConfirmationError::UnexpectedError)?
+        .ok_or(ConfirmationError::InvalidToken)?;
+    confirm_subscriber(&pool, subscriber_id)
+        .await
+        .context("Failed to update the subscriber status to `confirmed`.")
+        .map_err(ConfirmationError::UnexpectedError)?;
+    Ok(HttpResponse::Ok().json(SubscriptionStatus { status: "confirmed" }))
+}
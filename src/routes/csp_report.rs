@@ -0,0 +1,20 @@
+use actix_web::{web, HttpResponse};
+
+// Reports are small: anything bigger is not worth reading.
+pub const MAX_CSP_REPORT_BYTES: usize = 16 * 1024;
+
+#[utoipa::path(
+    post, path = "/csp-report", tag = "operations",
+    request_body(content = Object, content_type = "application/csp-report",
+        description = "A violation of the Content Security Policy, as reported by a browser"),
+    responses((status = 204, description = "The report has been logged"))
+)]
+#[tracing::instrument(name = "Log a Content Security Policy violation", skip_all)]
+pub async fn csp_report(body: web::Bytes) -> HttpResponse {
+    // Anybody can post here: log what is sent, but never fail on it.
+    match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(report) => tracing::warn!(%report, "Content Security Policy violation"),
+        Err(e) => tracing::info!(error.message = %e, "Ignored a malformed CSP report"),
+    }
+    HttpResponse::NoContent().finish()
+}
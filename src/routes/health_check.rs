@@ -1,5 +1,147 @@
-use actix_web::HttpResponse;
+use std::future::Future;
+use std::time::Duration;
 
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
+
+use crate::issue_delivery_worker::last_heartbeat;
+use crate::startup::RunMode;
+
+// How long a dependency gets to answer before it is reported as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+// The worker records a heartbeat every 10 seconds: a few missed beats mean it is gone.
+const WORKER_HEARTBEAT_MAX_AGE_SECONDS: i64 = 60;
+
+#[utoipa::path(
+    get, path = "/health_check", tag = "operations",
+    responses((status = 200, description = "The application is up"))
+)]
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Readiness {
+    #[schema(example = "ready")]
+    status: &'static str,
+    components: ReadinessComponents,
+}
+
+// Only the components the process uses are checked: a worker has no use for Redis,
+// and an API process does not depend on the worker being up.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ReadinessComponents {
+    postgres: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redis: Option<ComponentStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issue_delivery_worker: Option<WorkerStatus>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ComponentStatus {
+    #[schema(example = "up")]
+    status: &'static str,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct WorkerStatus {
+    #[schema(example = "up")]
+    status: &'static str,
+    // When the worker was last known to be alive, if ever.
+    last_heartbeat: Option<String>,
+}
+
+fn status(up: bool) -> &'static str {
+    if up {
+        "up"
+    } else {
+        "down"
+    }
+}
+
+// Runs a check, treating errors and timeouts as failures.
+async fn check<F>(component: &str, f: F) -> bool
+where
+    F: Future<Output = Result<(), anyhow::Error>>,
+{
+    match tokio::time::timeout(CHECK_TIMEOUT, f).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::warn!(error.cause_chain = ?e, component, "Readiness check failed");
+            false
+        }
+        Err(_) => {
+            tracing::warn!(component, "Readiness check timed out");
+            false
+        }
+    }
+}
+
+#[utoipa::path(
+    get, path = "/health/ready", tag = "operations",
+    responses(
+        (status = 200, description = "Every dependency is healthy", body = Readiness),
+        (status = 503, description = "At least one dependency is unhealthy", body = Readiness),
+    )
+)]
+#[tracing::instrument(name = "Check readiness", skip_all)]
+pub async fn readiness(
+    pool: web::Data<PgPool>,
+    redis_connection: Option<web::Data<ConnectionManager>>,
+    run_mode: web::Data<RunMode>,
+) -> HttpResponse {
+    let postgres_up = check("postgres", async {
+        sqlx::query("SELECT 1").execute(pool.get_ref()).await?;
+        Ok(())
+    })
+    .await;
+    // Worker processes are not given a Redis connection.
+    let redis_up = match redis_connection {
+        Some(redis_connection) => Some(
+            check("redis", async {
+                let mut connection = redis_connection.get_ref().clone();
+                redis::cmd("PING").query_async::<_, String>(&mut connection).await?;
+                Ok(())
+            })
+            .await,
+        ),
+        None => None,
+    };
+    let worker = if run_mode.runs_worker() { Some(check_worker(&pool).await) } else { None };
+
+    let ready = postgres_up
+        && redis_up.unwrap_or(true)
+        && worker.as_ref().is_none_or(|(worker_up, _)| *worker_up);
+    let body = Readiness {
+        status: if ready { "ready" } else { "unavailable" },
+        components: ReadinessComponents {
+            postgres: ComponentStatus { status: status(postgres_up) },
+            redis: redis_up.map(|up| ComponentStatus { status: status(up) }),
+            issue_delivery_worker: worker.map(|(worker_up, heartbeat)| WorkerStatus {
+                status: status(worker_up),
+                last_heartbeat: heartbeat.map(|at| at.to_rfc3339()),
+            }),
+        },
+    };
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+// Whether the worker has recorded a heartbeat recently, and when it last did.
+async fn check_worker(pool: &PgPool) -> (bool, Option<DateTime<Utc>>) {
+    let mut heartbeat = None;
+    let worker_up = check("issue_delivery_worker", async {
+        heartbeat = last_heartbeat(pool).await?;
+        Ok(())
+    })
+    .await
+        && heartbeat
+            .is_some_and(|at| (Utc::now() - at).num_seconds() <= WORKER_HEARTBEAT_MAX_AGE_SECONDS);
+    (worker_up, heartbeat)
+}
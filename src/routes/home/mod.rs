@@ -1,6 +1,26 @@
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpRequest, HttpResponse};
+use askama::Template;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok().content_type(ContentType::html()).body(include_str!("home.html"))
+use crate::i18n::{Locale, MessageId};
+use crate::security_headers::CspNonce;
+use crate::templates::render_page;
+
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomePage<'a> {
+    nonce: &'a str,
+    locale: Locale,
+}
+
+#[utoipa::path(
+    get, path = "/", tag = "web",
+    params(("Accept-Language" = Option<String>, Header, description = "The language to show the page in")),
+    responses((status = 200, description = "The home page", content_type = "text/html"))
+)]
+pub async fn home(
+    nonce: web::ReqData<CspNonce>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let locale = Locale::negotiate(None, &request);
+    render_page(&HomePage { nonce: nonce.as_str(), locale })
 }
@@ -0,0 +1,21 @@
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::templates::{flash_messages, render_page};
+
+#[derive(Template)]
+#[template(path = "login/forgot_password.html")]
+struct ForgotPasswordPage {
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get, path = "/login/forgot", tag = "web",
+    responses((status = 200, description = "The forgotten password form", content_type = "text/html"))
+)]
+pub async fn forgot_password_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render_page(&ForgotPasswordPage { flash_messages: self::flash_messages(&flash_messages) })
+}
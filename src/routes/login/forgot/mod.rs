@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{__path_forgot_password_form, forgot_password_form};
+pub use post::{__path_request_password_reset, request_password_reset};
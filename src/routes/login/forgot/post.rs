@@ -0,0 +1,84 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::{
+    generate_password_reset_token, get_user_id_by_email, store_password_reset_token,
+};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct FormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url),
+    fields(user_id = tracing::field::Empty)
+)]
+#[utoipa::path(
+    post, path = "/login/forgot", tag = "web",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the login form, whether or not the email is known"))
+)]
+pub async fn request_password_reset(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other("/login/forgot"));
+        }
+    };
+
+    // We answer the same way whether or not the address belongs to a user,
+    // so the form cannot be used to discover registered emails.
+    if let Some(user_id) = get_user_id_by_email(email.as_ref(), &pool).await.map_err(e500)? {
+        tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+        let token = generate_password_reset_token();
+        store_password_reset_token(user_id, &token, &pool).await.map_err(e500)?;
+        send_password_reset_email(&email_client, &email, &base_url.0, &token)
+            .await
+            .map_err(e500)?;
+    }
+
+    FlashMessage::info(
+        "If an account is registered with that email, a link to reset your password has been sent \
+         to it.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(name = "Send a password reset email", skip(email_client, recipient, token))]
+pub async fn send_password_reset_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    base_url: &str,
+    token: &Secret<String>,
+) -> Result<(), reqwest::Error> {
+    let reset_link = format!("{}/login/reset?token={}", base_url, token.expose_secret());
+    let html_body_text = format!(
+        "Someone asked to reset your password.<br />Click <a href=\"{}\">here</a> to choose a new \
+         one. The link expires in one hour.<br />If it was not you, ignore this email.",
+        reset_link
+    );
+    let plain_body_text = format!(
+        "Someone asked to reset your password.\nVisit {} to choose a new one. The link expires in \
+         one hour.\nIf it was not you, ignore this email.",
+        reset_link
+    );
+
+    email_client
+        .send_email(recipient, "Reset your password", &html_body_text, &plain_body_text)
+        .await
+}
@@ -1,42 +1,21 @@
-use std::fmt::Write;
-
-use actix_web::http::header::ContentType;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::templates::{flash_messages, render_page};
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
-    let mut error_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+#[derive(Template)]
+#[template(path = "login/login.html")]
+struct LoginPage {
+    flash_messages: Vec<String>,
+}
 
-    HttpResponse::Ok().content_type(ContentType::html()).body(format!(
-        r#"<!DOCTYPE html>
-            <html lang="en">
-            <head>
-                <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                <title>Login</title>
-            </head>
-            <body>
-                {error_html}
-                <form action="/login" method="post">
-                    <label>Username
-                        <input
-                            type="text"
-                            placeholder="Enter Username"
-                            name="username"
-                        >
-                    </label>
-                    <label>Password
-                        <input
-                            type="password"
-                            placeholder="Enter Password"
-                            name="password"
-                        >
-                    </label>
-                    <button type="submit">Login</button>
-                </form>
-            </body>
-            </html>"#,
-    ))
+#[utoipa::path(
+    get, path = "/login", tag = "web",
+    responses((status = 200, description = "The login form", content_type = "text/html"))
+)]
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render_page(&LoginPage { flash_messages: self::flash_messages(&flash_messages) })
 }
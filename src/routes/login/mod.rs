@@ -1,5 +1,9 @@
+mod forgot;
 mod get;
 mod post;
+mod reset;
 
-pub use get::login_form;
-pub use post::login;
+pub use forgot::*;
+pub use get::{__path_login_form, login_form};
+pub use post::{__path_login, login, LoginError};
+pub use reset::*;
@@ -1,46 +1,115 @@
 use actix_web::error::InternalError;
 use actix_web::http::header::LOCATION;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::authentication::{
+    get_session_generation, validate_credentials, AuthError, Credentials, LoginThrottle,
+    SessionIndex, SessionMetadata,
+};
+use crate::configuration::PasswordHashingSettings;
+use crate::csrf::generate_csrf_token;
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
+use crate::utils::client_ip;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
-    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+    skip(form, pool, session, throttle, session_index, hashing, request),
+    fields(
+        username=tracing::field::Empty,
+        user_id=tracing::field::Empty,
+        client_ip=tracing::field::Empty
+    )
+)]
+#[utoipa::path(
+    post, path = "/login", tag = "web",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the dashboard, or back to the login form on failure"))
 )]
-// We are now injecting `PgPool` to retrieve stored credentials from the database
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+    session_index: web::Data<SessionIndex>,
+    hashing: web::Data<PasswordHashingSettings>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
-    let credentials = Credentials { username: form.0.username, password: form.0.password };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &pool).await {
+    let username = form.0.username;
+    let client_ip = client_ip(&request);
+    tracing::Span::current()
+        .record("username", tracing::field::display(&username))
+        .record("client_ip", tracing::field::display(&client_ip));
+
+    // Locked out callers are turned away before we spend any time hashing their password.
+    if let Some(retry_after) = throttle
+        .lockout_remaining(&username, &client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        tracing::warn!(
+            retry_after_secs = retry_after.as_secs(),
+            "Rejected a locked out login attempt"
+        );
+        return Err(login_redirect(LoginError::TooManyAttempts(
+            retry_after.as_secs().div_ceil(60),
+        )));
+    }
+
+    let credentials = Credentials { username: username.clone(), password: form.0.password };
+    match validate_credentials(credentials, &hashing, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            throttle
+                .reset(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+
+            let session_generation = get_session_generation(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+                .unwrap_or_default();
 
             session.renew();
             session
                 .insert_user_id(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .insert_session_generation(session_generation)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .insert_csrf_token(&generate_csrf_token())
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+
+            let metadata = SessionMetadata::new(&request);
+            session_index
+                .register(user_id, &metadata)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            session
+                .insert_session_id(metadata.session_id)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
 
             Ok(HttpResponse::SeeOther().insert_header((LOCATION, "/admin/dashboard")).finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    throttle
+                        .register_failure(&username, &client_ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
 
@@ -53,6 +122,8 @@ pub async fn login(
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts. Please try again in {0} minute(s).")]
+    TooManyAttempts(u64),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -0,0 +1,33 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::templates::{flash_messages, render_page};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "login/reset_password.html")]
+struct ResetPasswordPage {
+    flash_messages: Vec<String>,
+    // Straight from the query string: the template escapes it like everything else.
+    token: String,
+}
+
+#[utoipa::path(
+    get, path = "/login/reset", tag = "web",
+    params(("token" = String, Query, description = "The token from the password reset email")),
+    responses((status = 200, description = "The password reset form", content_type = "text/html"))
+)]
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render_page(&ResetPasswordPage {
+        flash_messages: self::flash_messages(&flash_messages),
+        token: parameters.into_inner().token,
+    })
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{__path_reset_password_form, reset_password_form};
+pub use post::{__path_reset_password, reset_password};
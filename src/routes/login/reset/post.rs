@@ -0,0 +1,87 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::{
+    consume_password_reset_token, get_user_identity, invalidate_sessions, PasswordPolicy,
+    SessionIndex,
+};
+use crate::configuration::PasswordHashingSettings;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct FormData {
+    #[schema(value_type = String, format = Password)]
+    token: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Reset a forgotten password",
+    skip(form, pool, password_policy, hashing, session_index),
+    fields(user_id = tracing::field::Empty)
+)]
+#[utoipa::path(
+    post, path = "/login/reset", tag = "web",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the login form once the password has been reset"))
+)]
+pub async fn reset_password(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    hashing: web::Data<PasswordHashingSettings>,
+    session_index: web::Data<SessionIndex>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { token, new_password, new_password_check } = form.0;
+    let reset_form = format!("/login/reset?token={}", urlencoding::encode(token.expose_secret()));
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other(&reset_form));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let user_id =
+        match consume_password_reset_token(&token, &mut transaction).await.map_err(e500)? {
+            Some(user_id) => user_id,
+            None => {
+                FlashMessage::error(
+                    "The password reset link is invalid or has expired. Please request a new one.",
+                )
+                .send();
+                return Ok(see_other("/login/forgot"));
+            }
+        };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let (username, email) = get_user_identity(user_id, &mut transaction).await.map_err(e500)?;
+    let user_inputs: Vec<&str> =
+        std::iter::once(username.as_str()).chain(email.as_deref()).collect();
+    if let Err(violation) = password_policy.check(&new_password, None, &user_inputs) {
+        // Dropping the transaction keeps the link valid, so that the user can try again.
+        FlashMessage::error(violation.to_string()).send();
+        return Ok(see_other(&reset_form));
+    }
+
+    crate::authentication::change_password(user_id, new_password, &hashing, &mut transaction)
+        .await
+        .map_err(e500)?;
+    invalidate_sessions(user_id, &mut transaction).await.map_err(e500)?;
+    transaction.commit().await.context("Failed to commit SQL transaction").map_err(e500)?;
+    session_index.revoke_all(user_id, None).await.map_err(e500)?;
+
+    FlashMessage::info("Your password has been reset. You can now log in.").send();
+    Ok(see_other("/login"))
+}
@@ -0,0 +1,69 @@
+use actix_web::http::header::{CONTENT_TYPE, WWW_AUTHENTICATE};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use prometheus::{Encoder, TextEncoder};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::authentication::bearer_token;
+use crate::configuration::MetricsSettings;
+use crate::metrics::{
+    DB_POOL_CONNECTIONS, ISSUE_DELIVERY_QUEUE_DEPTH, ISSUE_DELIVERY_QUEUE_OLDEST_TASK_AGE_SECONDS,
+};
+use crate::utils::{constant_time_eq, e500};
+
+#[utoipa::path(
+    get, path = "/metrics", tag = "operations",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text exposition format"),
+        (status = 401, description = "The request does not carry the configured bearer token")
+    ),
+    security(("bearer" = []))
+)]
+#[tracing::instrument(name = "Export metrics", skip_all)]
+pub async fn metrics(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    settings: web::Data<MetricsSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let authorized = bearer_token(request.headers()).ok().flatten().is_some_and(|token| {
+        constant_time_eq(
+            token.expose_secret().as_bytes(),
+            settings.bearer_token.expose_secret().as_bytes(),
+        )
+    });
+    if !authorized {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    // Gauges describing the state of the system are refreshed on every scrape.
+    let queue = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "depth!",
+            COALESCE(EXTRACT(EPOCH FROM now() - MIN(enqueued_at)), 0)::BIGINT AS "oldest_task_age!"
+        FROM issue_delivery_queue
+        "#
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to measure the issue delivery queue.")
+    .map_err(e500)?;
+    ISSUE_DELIVERY_QUEUE_DEPTH.set(queue.depth);
+    ISSUE_DELIVERY_QUEUE_OLDEST_TASK_AGE_SECONDS.set(queue.oldest_task_age);
+
+    let size = pool.size() as i64;
+    let idle = pool.num_idle() as i64;
+    DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(idle);
+    DB_POOL_CONNECTIONS.with_label_values(&["in_use"]).set(size - idle);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["max"])
+        .set(pool.options().get_max_connections() as i64);
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&prometheus::gather(), &mut buffer).map_err(e500)?;
+    Ok(HttpResponse::Ok().insert_header((CONTENT_TYPE, encoder.format_type())).body(buffer))
+}
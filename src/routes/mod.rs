@@ -1,13 +1,19 @@
 mod admin;
+mod api;
+mod csp_report;
 mod health_check;
 mod home;
 mod login;
+mod metrics;
 mod subscriptions;
 mod subscriptions_confirm;
 
 pub use admin::*;
+pub use api::*;
+pub use csp_report::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use metrics::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
@@ -1,7 +1,9 @@
-use std::convert::{TryFrom, TryInto};
+use std::time::Duration;
 
+use actix_web::error::InternalError;
+use actix_web::http::header::{ContentType, HeaderName, RETRY_AFTER};
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use chrono::Utc;
 use rand::distributions::Alphanumeric;
@@ -11,48 +13,140 @@ use uuid::Uuid;
 
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
 use crate::email_client::EmailClient;
+use crate::i18n::{Locale, Message, MessageId};
+use crate::idempotency::RequestTransaction;
 use crate::startup::ApplicationBaseUrl;
+use crate::subscription_throttling::SubscriptionThrottle;
+use crate::utils::client_ip;
 
 #[allow(dead_code)]
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct FormData {
     email: String,
     name: String,
+    /// The language of the confirmation email, like `fr`; taken from `Accept-Language` when left
+    /// out
+    locale: Option<String>,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+// What the subscription form posts: `website` is a honeypot, hidden from people but
+// not from the bots filling in every field they find.
+#[derive(serde::Deserialize)]
+pub struct SubscriptionForm {
+    #[serde(flatten)]
+    data: FormData,
+    #[serde(default)]
+    website: String,
+}
 
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { name, email })
+impl FormData {
+    // Validates the form, settling the locale of the subscriber for `request`.
+    pub fn parse(self, request: &HttpRequest) -> Result<NewSubscriber, Message> {
+        let locale = Locale::negotiate(self.locale.as_deref(), request);
+        let name = SubscriberName::parse(self.name)?;
+        let email = SubscriberEmail::parse(self.email)?;
+        Ok(NewSubscriber { name, email, locale })
     }
 }
 
 #[allow(clippy::async_yields_async)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(form, pool, email_client, base_url, throttle, request),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_email = %form.data.email,
+        subscriber_name = %form.data.name
+    )
+)]
+#[utoipa::path(
+    post, path = "/subscriptions", tag = "web",
+    params((
+        "Idempotency-Key" = Option<String>, Header,
+        description = "Makes the request safe to retry: later requests with the same key get the first response"
+    )),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A confirmation email has been sent"),
+        (status = 400, description = "The name or the email is invalid"),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key has already been used for a different request"),
+        (status = 429, description = "Too many subscription requests from this client or for this address"),
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
+    form: web::Form<SubscriptionForm>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
-) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    throttle: web::Data<SubscriptionThrottle>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SubscriptionForm { data, website } = form.0;
+    // Bots get the same answer as everybody else, so that they do not learn to skip it.
+    if !website.is_empty() {
+        tracing::warn!("Ignored a subscription request that filled in the honeypot field");
+        return Ok(HttpResponse::Ok().finish());
+    }
+    // Errors are reported in the language of the form, even when it does not validate.
+    let locale = Locale::negotiate(data.locale.as_deref(), &request);
+    let outcome = async {
+        let new_subscriber = data.parse(&request).map_err(SubscribeError::ValidationError)?;
+        register_subscriber_within_limits(
+            new_subscriber,
+            &throttle,
+            &request,
+            &pool,
+            &email_client,
+            &base_url.0,
+        )
+        .await
+    };
+    match outcome.await {
+        Ok(()) => Ok(HttpResponse::Ok().finish()),
+        Err(e) => {
+            let response = e.localized_response(locale);
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
 
-    let mut transaction = pool.begin().await.context("Failed to acquire a Postgres connection")?;
+// Registers `new_subscriber`, unless the request goes over a limit of `throttle`.
+// The confirmation email cooldown only holds if an email has actually been sent:
+// otherwise the client could not retry until it is over.
+pub async fn register_subscriber_within_limits(
+    new_subscriber: NewSubscriber,
+    throttle: &SubscriptionThrottle,
+    request: &HttpRequest,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<(), SubscribeError> {
+    if let Some(retry_after) = throttle.check(&client_ip(request), &new_subscriber.email).await? {
+        return Err(SubscribeError::TooManyRequests(retry_after));
+    }
+    let recipient = new_subscriber.email.clone();
+    let outcome = register_subscriber(new_subscriber, request, pool, email_client, base_url).await;
+    if outcome.is_err() {
+        // A failure is logged: the worst case is the cooldown we meant to lift.
+        let _ = throttle.end_cooldown(&recipient).await;
+    }
+    outcome
+}
+
+// Stores a pending subscription and emails the subscriber a confirmation link.
+async fn register_subscriber(
+    new_subscriber: NewSubscriber,
+    request: &HttpRequest,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<(), SubscribeError> {
+    let mut transaction = RequestTransaction::begin(request, pool)
+        .await
+        .context("Failed to acquire a Postgres connection")?;
 
     if search_for_existing_subscription(&new_subscriber, &mut transaction).await.is_err() {
-        return Err(SubscribeError::ValidationError(
-            "The provided email is already subscribed".into(),
-        ));
+        return Err(SubscribeError::ValidationError(Message::new(MessageId::AlreadySubscribed)));
     }
 
     let subscriber_id = insert_subscriber(&new_subscriber, &mut transaction)
@@ -65,19 +159,23 @@ pub async fn subscribe(
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
 
-    transaction.commit().await.context("Failed to commit SQL transaction")?;
-
-    send_confirmation_email(&email_client, new_subscriber, &base_url.0, &subscription_token)
+    // The subscription is only stored once the email has gone out: if it cannot be
+    // sent, the subscriber can try again from scratch.
+    send_confirmation_email(email_client, new_subscriber, base_url, &subscription_token)
         .await
         .context("Failed to send a confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    transaction.commit().await.context("Failed to commit SQL transaction")?;
+
+    Ok(())
 }
 
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
-    ValidationError(String),
+    ValidationError(Message),
+    #[error("Too many subscription requests - please try again later.")]
+    TooManyRequests(Duration),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -86,9 +184,46 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after() {
+            response.insert_header(retry_after);
+        }
+        response.content_type(ContentType::plaintext()).body(self.to_string())
+    }
+}
+
+impl SubscribeError {
+    // The error response, with a message in `locale` rather than in English.
+    pub fn localized_response(&self, locale: Locale) -> HttpResponse {
+        let message = match self {
+            SubscribeError::ValidationError(message) => message.clone(),
+            SubscribeError::TooManyRequests(_) => {
+                Message::new(MessageId::TooManySubscriptionRequests)
+            }
+            SubscribeError::UnexpectedError(_) => Message::new(MessageId::UnexpectedError),
+        };
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after() {
+            response.insert_header(retry_after);
+        }
+        response.content_type(ContentType::plaintext()).body(message.localize(locale))
+    }
+
+    // The `Retry-After` header to send along with the error, if any.
+    pub fn retry_after(&self) -> Option<(HeaderName, String)> {
+        match self {
+            SubscribeError::TooManyRequests(retry_after) => {
+                Some((RETRY_AFTER, retry_after.as_secs().max(1).to_string()))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Debug for SubscribeError {
@@ -109,19 +244,17 @@ pub async fn send_confirmation_email(
 ) -> Result<(), reqwest::Error> {
     let confirmation_link =
         format!("{}/subscriptions/confirm?subscription_token={}", base_url, subscription_token);
-    let html_body_text = format!(
-        "Welcome to our newsletter!<br />Click <a href=\"{}\">here</a> to confirm your \
-         subscription.",
-        confirmation_link
-    );
-
-    let plain_body_text = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
+    let locale = new_subscriber.locale;
+    let subject = locale.translate(MessageId::ConfirmationEmailSubject);
+    let html_body_text = Message::new(MessageId::ConfirmationEmailHtml)
+        .with("link", &confirmation_link)
+        .localize(locale);
+    let plain_body_text = Message::new(MessageId::ConfirmationEmailText)
+        .with("link", confirmation_link)
+        .localize(locale);
 
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body_text, &plain_body_text)
+        .send_email(&new_subscriber.email, &subject, &html_body_text, &plain_body_text)
         .await
 }
 
@@ -136,13 +269,14 @@ async fn insert_subscriber(
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, locale)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        new_subscriber.locale.as_str()
     )
     .execute(&mut *(*transaction))
     .await?;
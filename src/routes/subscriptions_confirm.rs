@@ -1,13 +1,17 @@
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use askama::Template;
 use reqwest::StatusCode;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::i18n::{Locale, MessageId};
 use crate::routes::error_chain_fmt;
+use crate::templates::render_page;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct Parameters {
-    subscription_token: String,
+    pub subscription_token: String,
 }
 
 #[derive(thiserror::Error)]
@@ -21,8 +25,8 @@ pub enum ConfirmationError {
 impl ResponseError for ConfirmationError {
     fn status_code(&self) -> StatusCode {
         match self {
-            Self::UnexpectedError(_) => StatusCode::UNAUTHORIZED,
-            Self::InvalidToken => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
         }
     }
 }
@@ -33,37 +37,60 @@ impl std::fmt::Debug for ConfirmationError {
     }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
+#[derive(Template)]
+#[template(path = "subscription_confirmed.html")]
+struct SubscriptionConfirmedPage {
+    locale: Locale,
+}
+
+#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool, request))]
+#[utoipa::path(
+    get, path = "/subscriptions/confirm", tag = "web",
+    params(
+        ("subscription_token" = String, Query, description = "The token from the confirmation email"),
+        ("Accept-Language" = Option<String>, Header, description = "The language of the error page; the confirmation page is shown in the language of the subscriber"),
+    ),
+    responses(
+        (status = 200, description = "The subscription has been confirmed", content_type = "text/html"),
+        (status = 401, description = "The token is unknown"),
+    )
+)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
     let id = match get_subscriber_id_from_token(&parameters.subscription_token, &pool).await {
         Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+        Err(_) => return Ok(HttpResponse::InternalServerError().finish()),
     };
 
     match id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
-            }
-            HttpResponse::Ok().finish()
+        None => {
+            let locale = Locale::negotiate(None, &request);
+            Ok(HttpResponse::Unauthorized()
+                .content_type(ContentType::plaintext())
+                .body(locale.translate(MessageId::InvalidSubscriptionToken)))
         }
+        Some(subscriber_id) => match confirm_subscriber(&pool, subscriber_id).await {
+            Ok(locale) => render_page(&SubscriptionConfirmedPage { locale }),
+            Err(_) => Ok(HttpResponse::InternalServerError().finish()),
+        },
     }
 }
 
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(pool, subscriber_id))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    let confirmed =
-        sqlx::query!(r#"SELECT status FROM subscriptions WHERE id = $1"#, subscriber_id)
+// Returns the locale of the subscriber.
+pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<Locale, sqlx::Error> {
+    let subscriber =
+        sqlx::query!(r#"SELECT status, locale FROM subscriptions WHERE id = $1"#, subscriber_id)
             .fetch_one(pool)
             .await
             .map_err(|e| {
                 tracing::error!("Failed to execute query: {:?}", e);
                 e
-            })?
-            .status
-            == "confirmed";
-    if confirmed {
+            })?;
+    if subscriber.status == "confirmed" {
         return Err(sqlx::Error::RowNotFound);
     }
 
@@ -74,12 +101,12 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
             tracing::error!("Failed to execute query: {:?}", e);
             e
         })?;
-    Ok(())
+    Ok(Locale::parse(&subscriber.locale).unwrap_or_default())
 }
 
 /// Get subscriber token from the database
 #[tracing::instrument(name = "Getting subscriber ID from token", skip(token, pool))]
-async fn get_subscriber_id_from_token(
+pub async fn get_subscriber_id_from_token(
     token: &str,
     pool: &PgPool,
 ) -> Result<Option<Uuid>, sqlx::Error> {
@@ -0,0 +1,115 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{
+    HeaderName, HeaderValue, InvalidHeaderValue, CONTENT_SECURITY_POLICY,
+    CONTENT_SECURITY_POLICY_REPORT_ONLY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::configuration::SecurityHeadersSettings;
+use crate::utils::e500;
+
+// Where browsers send the violations of the policy.
+pub const CSP_REPORT_PATH: &str = "/csp-report";
+
+/// The nonce that the inline `<script>` and `<style>` elements of the page being
+/// rendered have to carry as `nonce="..."`. Handlers get it as `web::ReqData<CspNonce>`.
+#[derive(Clone)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        Self(STANDARD.encode(rand::random::<[u8; 16]>()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The security headers we add to every response, ready to be sent.
+pub struct SecurityHeaders {
+    content_security_policy: String,
+    csp_header: HeaderName,
+    frame_options: HeaderValue,
+    referrer_policy: HeaderValue,
+    strict_transport_security: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: &SecurityHeadersSettings) -> Result<Self, InvalidHeaderValue> {
+        let csp_header = if settings.csp_report_only {
+            CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            CONTENT_SECURITY_POLICY
+        };
+        let strict_transport_security = match settings.hsts_max_age_seconds {
+            0 => None,
+            max_age => {
+                Some(HeaderValue::from_str(&format!("max-age={}; includeSubDomains", max_age))?)
+            }
+        };
+        Ok(Self {
+            content_security_policy: format!(
+                "{}; report-uri {}",
+                settings.content_security_policy, CSP_REPORT_PATH
+            ),
+            csp_header,
+            frame_options: HeaderValue::from_str(&settings.frame_options)?,
+            referrer_policy: HeaderValue::from_str(&settings.referrer_policy)?,
+            strict_transport_security,
+        })
+    }
+
+    fn content_security_policy(&self, nonce: &CspNonce) -> Result<HeaderValue, InvalidHeaderValue> {
+        let nonce = format!("'nonce-{}'", nonce);
+        HeaderValue::from_str(&self.content_security_policy.replace("{nonce}", &nonce))
+    }
+}
+
+/// Give every request a fresh `CspNonce` and add the security headers to its response.
+///
+/// Headers that the handler has set itself are left alone: a page that needs a
+/// different policy, like the API explorer, sends its own.
+pub async fn set_security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let headers = req
+        .app_data::<web::Data<SecurityHeaders>>()
+        .ok_or_else(|| e500("Missing SecurityHeaders"))?
+        .clone();
+    let nonce = CspNonce::generate();
+    req.extensions_mut().insert(nonce.clone());
+
+    let mut response = next.call(req).await?;
+    let csp = headers.content_security_policy(&nonce).map_err(e500)?;
+    let response_headers = response.headers_mut();
+    let has_csp = response_headers.contains_key(CONTENT_SECURITY_POLICY)
+        || response_headers.contains_key(CONTENT_SECURITY_POLICY_REPORT_ONLY);
+    let mut insert = |name: HeaderName, value: HeaderValue| {
+        if !response_headers.contains_key(&name) {
+            response_headers.insert(name, value);
+        }
+    };
+    if !has_csp {
+        insert(headers.csp_header.clone(), csp);
+    }
+    insert(X_FRAME_OPTIONS, headers.frame_options.clone());
+    insert(REFERRER_POLICY, headers.referrer_policy.clone());
+    insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if let Some(hsts) = &headers.strict_transport_security {
+        insert(STRICT_TRANSPORT_SECURITY, hsts.clone());
+    }
+    Ok(response)
+}
@@ -13,6 +13,12 @@ pub struct TypedSession(Session);
 impl TypedSession {
     // The key used to store the user ID in the session.
     const USER_ID_KEY: &'static str = "user_id";
+    // The key used to store the user's session generation at login time.
+    const SESSION_GENERATION_KEY: &'static str = "session_generation";
+    // The key used to store the id of the session in the `SessionIndex`.
+    const SESSION_ID_KEY: &'static str = "session_id";
+    // The key used to store the token admin forms have to send back.
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
 
     // Renews the session key, assigning existing session state to new key.
     pub fn renew(&self) {
@@ -31,6 +37,42 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    // Inserts the session generation of the user into the session.
+    // Returns an error if it fails to serialize value to JSON.
+    pub fn insert_session_generation(&self, generation: i32) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_GENERATION_KEY, generation)
+    }
+
+    // Gets the session generation from the session.
+    // Sessions created before generations were tracked belong to generation 0.
+    pub fn get_session_generation(&self) -> Result<i32, SessionGetError> {
+        Ok(self.0.get(Self::SESSION_GENERATION_KEY)?.unwrap_or(0))
+    }
+
+    // Inserts the id under which the session is tracked in the `SessionIndex`.
+    // Returns an error if it fails to serialize value to JSON.
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    // Gets the id under which the session is tracked in the `SessionIndex`.
+    // Returns an error if it fails to deserialize value from JSON.
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    // Inserts the token that state-changing requests have to carry.
+    // Returns an error if it fails to serialize value to JSON.
+    pub fn insert_csrf_token(&self, token: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::CSRF_TOKEN_KEY, token)
+    }
+
+    // Gets the token that state-changing requests have to carry.
+    // Returns an error if it fails to deserialize value from JSON.
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
     // Removes the user ID from the session.
     pub fn log_out(self) {
         self.0.purge()
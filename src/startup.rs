@@ -1,25 +1,59 @@
-use std::net::TcpListener;
+use std::net::{IpAddr, TcpListener};
 
+use actix_session::config::CookieContentSecurity;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
-use actix_web::cookie::Key;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_lab::middleware::from_fn;
-use secrecy::{ExposeSecret, Secret};
+use redis::aio::ConnectionManager;
+use secrecy::ExposeSecret;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
 
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{reject_anonymous_users, LoginThrottle, PasswordPolicy, SessionIndex};
+use crate::configuration::{DatabaseSettings, MetricsSettings, Settings};
+use crate::cookie_keys::{resign_cookies, CookieKeys};
+use crate::csrf::verify_csrf_token;
 use crate::email_client::EmailClient;
+use crate::idempotency::handle_idempotency_key;
+use crate::metrics::record_http_metrics;
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, confirm, health_check, home, log_out,
-    login, login_form, publish_newsletter, publish_newsletter_form, subscribe,
+    admin_dashboard, api_confirm, api_explorer, api_get_newsletter_issue, api_list_subscribers,
+    api_publish_newsletter, api_subscribe, change_password, change_password_form, confirm,
+    confirm_replayed_publication, create_api_token, csp_report, forgot_password_form, health_check,
+    home, json_error_handler, list_api_tokens, list_sessions, log_out, login, login_form, metrics,
+    openapi_json, publish_newsletter, publish_newsletter_form, readiness, request_password_reset,
+    require_api_token, reset_password, reset_password_form, revoke_all_sessions, revoke_api_token,
+    revoke_session, subscribe, MAX_CSP_REPORT_BYTES,
 };
+use crate::security_headers::{set_security_headers, SecurityHeaders};
+use crate::subscription_throttling::SubscriptionThrottle;
+
+/// What a process runs. Its readiness only depends on what it runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunMode {
+    /// The API and the background work.
+    #[default]
+    All,
+    Api,
+    /// Only the background work: the HTTP server is limited to the health checks and
+    /// the metrics.
+    Worker,
+}
+
+impl RunMode {
+    pub fn serves_api(self) -> bool {
+        self != RunMode::Worker
+    }
+
+    pub fn runs_worker(self) -> bool {
+        self != RunMode::Api
+    }
+}
 
 pub struct Application {
     port: u16,
@@ -27,25 +61,20 @@ pub struct Application {
 }
 
 impl Application {
-    pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+    pub async fn build(configuration: Settings, run_mode: RunMode) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
 
-        let email_client = configuration.email_client.client();
-
         let address =
             format!("{}:{}", configuration.application.host, configuration.application.port);
 
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = if run_mode.serves_api() {
+            let email_client = configuration.email_client.clone().client();
+            run(listener, connection_pool, email_client, configuration, run_mode).await?
+        } else {
+            run_operations(listener, connection_pool, configuration.metrics, run_mode)?
+        };
         Ok(Self { port, server })
     }
 
@@ -66,55 +95,192 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub struct ApplicationBaseUrl(pub String);
 
+// See `ApplicationSettings::trusted_proxies`.
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    configuration: Settings,
+    run_mode: RunMode,
 ) -> Result<Server, anyhow::Error> {
+    let Settings {
+        application,
+        redis_uri,
+        login_throttling,
+        subscription_throttling,
+        password_policy,
+        password_hashing,
+        idempotency,
+        security_headers,
+        cookies,
+        metrics: metrics_settings,
+        ..
+    } = configuration;
+    let cookie_keys = CookieKeys::new(&application.hmac_secret, &application.previous_hmac_secrets);
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
-    let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
-    let message_store = CookieMessageStore::builder(secret_key.clone()).build();
+    let secret_key = cookie_keys.current();
+    let cookie_keys = web::Data::new(cookie_keys);
+    let cookie_settings = web::Data::new(cookies.clone());
+    let base_url = web::Data::new(ApplicationBaseUrl(application.base_url));
+    let trusted_proxies = web::Data::new(TrustedProxies(application.trusted_proxies));
+    let message_store =
+        CookieMessageStore::builder(secret_key.clone()).path(cookies.path.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_connection =
+        ConnectionManager::new(redis::Client::open(redis_uri.expose_secret().as_str())?).await?;
+    let login_throttle =
+        web::Data::new(LoginThrottle::new(redis_connection.clone(), login_throttling));
+    let subscription_throttle = web::Data::new(SubscriptionThrottle::new(
+        redis_connection.clone(),
+        subscription_throttling,
+    ));
+    let session_index = web::Data::new(SessionIndex::new(redis_connection.clone()));
+    let redis_connection = web::Data::new(redis_connection);
+    let password_policy = web::Data::new(PasswordPolicy::from(password_policy));
+    let password_hashing = web::Data::new(password_hashing);
+    let idempotency = web::Data::new(idempotency);
+    let security_headers = web::Data::new(SecurityHeaders::new(&security_headers)?);
+    let metrics_settings = web::Data::new(metrics_settings);
+    let run_mode = web::Data::new(run_mode);
     let server = HttpServer::new(move || {
         App::new()
             // Middleware logger added here
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                // Spelled out rather than left to the defaults: the CSRF protection of
+                // the admin forms relies on them, and `resign_cookies` uses them too.
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .cookie_content_security(CookieContentSecurity::Private)
+                    .cookie_path(cookies.path.clone())
+                    .cookie_secure(cookies.secure)
+                    .cookie_http_only(true)
+                    .cookie_same_site(cookies.same_site.into())
+                    .build(),
+            )
+            .wrap(from_fn(resign_cookies))
+            .wrap(from_fn(set_security_headers))
+            .wrap(from_fn(record_http_metrics))
             .wrap(TracingLogger::default())
             .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
+            .route("/health/ready", web::get().to(readiness))
+            .route("/metrics", web::get().to(metrics))
+            .service(
+                web::resource("/csp-report")
+                    .app_data(web::PayloadConfig::new(MAX_CSP_REPORT_BYTES))
+                    .route(web::post().to(csp_report)),
+            )
+            .service(
+                web::resource("/subscriptions")
+                    .wrap(from_fn(handle_idempotency_key))
+                    .route(web::post().to(subscribe)),
+            )
             .route("/subscriptions/confirm", web::get().to(confirm))
             .route("/", web::get().to(home))
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/forgot", web::get().to(forgot_password_form))
+            .route("/login/forgot", web::post().to(request_password_reset))
+            .route("/login/reset", web::get().to(reset_password_form))
+            .route("/login/reset", web::post().to(reset_password))
+            .route("/api/openapi.json", web::get().to(openapi_json))
+            .route("/api/docs", web::get().to(api_explorer))
+            .service(
+                web::scope("/api/v1")
+                    .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                    .service(
+                        web::resource("/subscriptions")
+                            .wrap(from_fn(handle_idempotency_key))
+                            .route(web::post().to(api_subscribe)),
+                    )
+                    .route("/subscriptions/confirm", web::post().to(api_confirm))
+                    .service(
+                        web::resource("/subscribers")
+                            .wrap(from_fn(require_api_token))
+                            .route(web::get().to(api_list_subscribers)),
+                    )
+                    .service(
+                        web::resource("/newsletters")
+                            .wrap(from_fn(handle_idempotency_key))
+                            .wrap(from_fn(require_api_token))
+                            .route(web::post().to(api_publish_newsletter)),
+                    )
+                    .service(
+                        web::resource("/newsletters/{issue_id}")
+                            .wrap(from_fn(require_api_token))
+                            .route(web::get().to(api_get_newsletter_issue)),
+                    ),
+            )
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(verify_csrf_token))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
                     .route("/logout", web::post().to(log_out))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/newsletters", web::get().to(publish_newsletter_form)),
+                    .route("/sessions", web::get().to(list_sessions))
+                    .route("/sessions/revoke", web::post().to(revoke_session))
+                    .route("/sessions/revoke_all", web::post().to(revoke_all_sessions))
+                    .route("/tokens", web::get().to(list_api_tokens))
+                    .route("/tokens", web::post().to(create_api_token))
+                    .route("/tokens/revoke", web::post().to(revoke_api_token))
+                    .service(
+                        web::resource("/newsletters")
+                            .wrap(from_fn(handle_idempotency_key))
+                            .wrap(from_fn(confirm_replayed_publication))
+                            .route(web::post().to(publish_newsletter))
+                            .route(web::get().to(publish_newsletter_form)),
+                    ),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
-            .app_data(web::Data::new(HmacSecret(hmac_secret.clone())))
+            .app_data(trusted_proxies.clone())
+            .app_data(login_throttle.clone())
+            .app_data(subscription_throttle.clone())
+            .app_data(session_index.clone())
+            .app_data(redis_connection.clone())
+            .app_data(password_policy.clone())
+            .app_data(password_hashing.clone())
+            .app_data(idempotency.clone())
+            .app_data(security_headers.clone())
+            .app_data(cookie_keys.clone())
+            .app_data(cookie_settings.clone())
+            .app_data(metrics_settings.clone())
+            .app_data(run_mode.clone())
     })
     .listen(listener)?
     .run();
     Ok(server)
 }
 
-#[derive(Clone)]
-pub struct HmacSecret(pub Secret<String>);
+// The endpoints of a worker process, which has no API to serve but still has to be
+// probed and scraped.
+fn run_operations(
+    listener: TcpListener,
+    db_pool: PgPool,
+    metrics_settings: MetricsSettings,
+    run_mode: RunMode,
+) -> Result<Server, anyhow::Error> {
+    let db_pool = web::Data::new(db_pool);
+    let metrics_settings = web::Data::new(metrics_settings);
+    let run_mode = web::Data::new(run_mode);
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(from_fn(record_http_metrics))
+            .wrap(TracingLogger::default())
+            .route("/health_check", web::get().to(health_check))
+            .route("/health/ready", web::get().to(readiness))
+            .route("/metrics", web::get().to(metrics))
+            .app_data(db_pool.clone())
+            .app_data(metrics_settings.clone())
+            .app_data(run_mode.clone())
+    })
+    .listen(listener)?
+    .run();
+    Ok(server)
+}
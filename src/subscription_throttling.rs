@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use crate::configuration::SubscriptionThrottlingSettings;
+use crate::domain::SubscriberEmail;
+
+// Purpose: Keeps `/subscriptions` from being used to flood arbitrary addresses with
+// confirmation emails. Requests are counted in Redis per client IP and per recipient,
+// over fixed windows, and a recipient gets at most one email per cooldown.
+#[derive(Clone)]
+pub struct SubscriptionThrottle {
+    connection: ConnectionManager,
+    settings: SubscriptionThrottlingSettings,
+}
+
+impl SubscriptionThrottle {
+    pub fn new(connection: ConnectionManager, settings: SubscriptionThrottlingSettings) -> Self {
+        Self { connection, settings }
+    }
+
+    // Counts a subscription request, returning how long the caller has to wait before
+    // trying again if it goes over a limit. Requests over the per-IP limit are not
+    // counted against the recipient.
+    #[tracing::instrument(name = "Check subscription rate limits", skip(self))]
+    pub async fn check(
+        &self,
+        client_ip: &str,
+        recipient: &SubscriberEmail,
+    ) -> Result<Option<Duration>, anyhow::Error> {
+        let recipient = recipient.as_ref().to_lowercase();
+        let counters = [
+            (
+                format!("subscriptions:ip:{}", client_ip),
+                self.settings.max_requests_per_ip,
+                self.settings.ip_window_seconds,
+            ),
+            (
+                format!("subscriptions:recipient:{}", recipient),
+                self.settings.max_requests_per_recipient,
+                self.settings.recipient_window_seconds,
+            ),
+        ];
+        for (key, max_requests, window_seconds) in counters {
+            if let Some(retry_after) = self.count(&key, max_requests, window_seconds).await? {
+                tracing::warn!(limit_key = %key, "Rejected a subscription request over the limit");
+                return Ok(Some(retry_after));
+            }
+        }
+        self.start_cooldown(&recipient).await
+    }
+
+    // Returns the time left in the window if the counter went over `max_requests`.
+    async fn count(
+        &self,
+        key: &str,
+        max_requests: u64,
+        window_seconds: u64,
+    ) -> Result<Option<Duration>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        // The window starts with the first request: later ones do not push it back.
+        let (requests, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(window_seconds)
+            .ignore()
+            .incr(key, 1)
+            .ttl(key)
+            .query_async(&mut connection)
+            .await
+            .context("Failed to increment a subscription request counter.")?;
+        Ok((requests > max_requests).then(|| Duration::from_secs(ttl.max(1) as u64)))
+    }
+
+    // Claims the right to email `recipient`, unless it has been emailed too recently.
+    async fn start_cooldown(&self, recipient: &str) -> Result<Option<Duration>, anyhow::Error> {
+        let cooldown_seconds = self.settings.confirmation_email_cooldown_seconds;
+        if cooldown_seconds == 0 {
+            return Ok(None);
+        }
+        let mut connection = self.connection.clone();
+        let key = format!("subscriptions:cooldown:{}", recipient);
+        let claimed: bool = redis::cmd("SET")
+            .arg(&key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(cooldown_seconds)
+            .query_async::<_, Option<String>>(&mut connection)
+            .await
+            .context("Failed to start a confirmation email cooldown.")?
+            .is_some();
+        if claimed {
+            return Ok(None);
+        }
+        let ttl: i64 = connection.ttl(&key).await.context("Failed to read a cooldown expiry.")?;
+        tracing::warn!("Rejected a subscription request during the confirmation email cooldown");
+        Ok(Some(Duration::from_secs(ttl.max(1) as u64)))
+    }
+
+    // Lifts the cooldown claimed by `check`, for requests that did not email `recipient`
+    // after all.
+    #[tracing::instrument(name = "End confirmation email cooldown", skip(self), err)]
+    pub async fn end_cooldown(&self, recipient: &SubscriberEmail) -> Result<(), anyhow::Error> {
+        if self.settings.confirmation_email_cooldown_seconds == 0 {
+            return Ok(());
+        }
+        let mut connection = self.connection.clone();
+        let key = format!("subscriptions:cooldown:{}", recipient.as_ref().to_lowercase());
+        connection
+            .del::<_, ()>(&key)
+            .await
+            .context("Failed to end a confirmation email cooldown.")?;
+        Ok(())
+    }
+}
@@ -1,29 +1,88 @@
+use std::collections::HashMap;
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceContextExt, TraceError, TracerProvider as _};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{config, Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
 use tokio::task::JoinHandle;
 use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
+use tracing::{Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
+use crate::configuration::OtlpSettings;
+
+// Spans are always given OpenTelemetry trace ids, so that trace context can be
+// propagated; they are only exported when `otlp` is provided.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+    otlp: Option<&OtlpSettings>,
+) -> Result<impl Subscriber + Send + Sync, TraceError>
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let tracer = get_tracer(&name, otlp)?;
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
-    Registry::default().with(env_filter).with(JsonStorageLayer).with(formatting_layer)
+    Ok(Registry::default()
+        .with(env_filter)
+        .with(JsonStorageLayer)
+        .with(formatting_layer)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+fn get_tracer(name: &str, otlp: Option<&OtlpSettings>) -> Result<Tracer, TraceError> {
+    let mut provider = TracerProvider::builder().with_config(
+        config().with_resource(Resource::new([KeyValue::new("service.name", name.to_owned())])),
+    );
+    if let Some(otlp) = otlp {
+        let exporter = opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(&otlp.endpoint)
+            .build_span_exporter()?;
+        provider = provider.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    let provider = provider.build();
+    let tracer = provider.tracer(name.to_owned());
+    global::set_tracer_provider(provider);
+    Ok(tracer)
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger");
     set_global_default(subscriber).expect("Failed to set subscriber");
+    // Incoming `traceparent` headers are picked up by `TracingLogger`.
+    global::set_text_map_propagator(TraceContextPropagator::new());
+}
+
+// Serializes the trace context of the current span, for work that is handed over to
+// another task - e.g. through a queue in the database.
+pub fn current_trace_context() -> String {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+    serde_json::to_string(&carrier).expect("Failed to serialize a trace context")
+}
+
+// Links `span` to the trace a piece of work was handed over from.
+pub fn link_to_trace_context(span: &Span, trace_context: &str) {
+    let Ok(carrier) = serde_json::from_str::<HashMap<String, String>>(trace_context) else {
+        return;
+    };
+    let context = TraceContextPropagator::new().extract(&carrier);
+    let span_context = context.span().span_context().clone();
+    if span_context.is_valid() {
+        span.add_link(span_context);
+    }
 }
 
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
@@ -0,0 +1,42 @@
+use actix_web::http::header::ContentType;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::utils::e500;
+
+// Renders `page` as the body of a 200 response.
+// Pages are askama templates in `templates/`: they extend `base.html`, are checked
+// when the crate is compiled and HTML-escape every value they interpolate.
+pub fn render_page(page: &impl Template) -> Result<HttpResponse, actix_web::Error> {
+    let html = page.render().map_err(e500)?;
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(html))
+}
+
+// The messages `flash_messages.html` shows at the top of a page.
+pub fn flash_messages(incoming: &IncomingFlashMessages) -> Vec<String> {
+    incoming.iter().map(|m| m.content().to_owned()).collect()
+}
+
+// The filters available to our templates. Templates find them through
+// `use crate::templates::filters;` in the module declaring them.
+pub mod filters {
+    use chrono::{DateTime, Utc};
+
+    use crate::authentication::ApiTokenScope;
+
+    const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+    pub fn timestamp(t: &DateTime<Utc>) -> askama::Result<String> {
+        Ok(t.format(TIMESTAMP_FORMAT).to_string())
+    }
+
+    // Like `timestamp`, showing `default` when there is no timestamp.
+    pub fn timestamp_or(t: &Option<DateTime<Utc>>, default: &str) -> askama::Result<String> {
+        Ok(t.map(|t| t.format(TIMESTAMP_FORMAT).to_string()).unwrap_or_else(|| default.into()))
+    }
+
+    pub fn scopes(scopes: &[ApiTokenScope]) -> askama::Result<String> {
+        Ok(scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "))
+    }
+}
@@ -1,15 +1,31 @@
-use actix_web::http::header::LOCATION;
-use actix_web::HttpResponse;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::error::PayloadError;
+use actix_web::http::header::{HeaderName, LOCATION};
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::{future, stream, Stream};
+
+use crate::startup::TrustedProxies;
 
 // Return a 400 with the user-representation of the validation error as body.
 // The error root cause is preserved for logging purposes.
-pub fn e400<T: std::fmt::Debug + std::fmt::Display>(e: T) -> actix_web::Error
+pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
     actix_web::error::ErrorBadRequest(e)
 }
 
+// Return a 422 for requests that are well-formed but cannot be honoured as sent.
+pub fn e422<T>(e: T) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    actix_web::error::ErrorUnprocessableEntity(e)
+}
+
 // Return an opaque 500 while preserving the error root's cause for logging.
 pub fn e500<T>(e: T) -> actix_web::Error
 where
@@ -21,3 +37,61 @@ where
 pub fn see_other(location: &str) -> HttpResponse {
     HttpResponse::SeeOther().insert_header((LOCATION, location)).finish()
 }
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+// The address of the client.
+// That is the peer of the connection, unless the peer is one of our trusted proxies:
+// then it is the last address in `X-Forwarded-For` that was not added by one of them,
+// since the client can put anything it likes at the front of the header.
+pub fn client_ip(request: &HttpRequest) -> String {
+    let Some(peer) = request.peer_addr().map(|addr| addr.ip()) else {
+        return "unknown".into();
+    };
+    let Some(trusted_proxies) = request.app_data::<web::Data<TrustedProxies>>() else {
+        return peer.to_string();
+    };
+    if !trusted_proxies.0.contains(&peer) {
+        return peer.to_string();
+    }
+    let forwarded_for = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(|ip| ip.trim().parse::<IpAddr>())
+        .collect::<Vec<_>>();
+    let mut client = peer;
+    for ip in forwarded_for.into_iter().rev() {
+        match ip {
+            Ok(ip) if trusted_proxies.0.contains(&client) => client = ip,
+            _ => break,
+        }
+    }
+    client.to_string()
+}
+
+// Turn a body that a middleware has read back into a payload for the handler.
+pub fn bytes_to_payload(body: web::Bytes) -> Payload {
+    let stream: Pin<Box<dyn Stream<Item = Result<web::Bytes, PayloadError>>>> =
+        Box::pin(stream::once(future::ready(Ok(body))));
+    Payload::from(stream)
+}
+
+// Compares two tokens in a time that does not depend on where they differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constant_time_eq;
+
+    #[test]
+    fn tokens_are_compared_in_full() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+        assert!(!constant_time_eq(b"", b"abc"));
+    }
+}
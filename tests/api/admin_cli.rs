@@ -0,0 +1,215 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp};
+
+// Runs the admin binary against the database of `app`, feeding `stdin` to it.
+async fn run_admin(app: &TestApp, args: &[&str], stdin: &str) -> Output {
+    let database_name: String =
+        sqlx::query_scalar("SELECT current_database()").fetch_one(&app.db_pool).await.unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_robust-rust-admin"))
+        .args(args)
+        .env("APP_DATABASE__DATABASE_NAME", database_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the admin binary.");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+async fn login(app: &TestApp, username: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({ "username": username, "password": password })).await
+}
+
+#[tokio::test]
+async fn a_created_user_can_log_in() {
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+    let password = "a brand new passphrase";
+
+    let output = run_admin(
+        &app,
+        &["users", "create", &username, "--email", "new-user@example.com"],
+        &format!("{}\n", password),
+    )
+    .await;
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains(&format!("Created user {}", username)));
+    let response = login(&app, &username, password).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
+
+#[tokio::test]
+async fn creating_a_user_with_a_weak_password_fails() {
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+
+    let output = run_admin(&app, &["users", "create", &username], "password\n").await;
+
+    assert!(!output.status.success());
+    let n_users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username = $1")
+        .bind(&username)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_users, 0);
+}
+
+#[tokio::test]
+async fn creating_a_user_with_an_invalid_email_fails() {
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+
+    let output = run_admin(
+        &app,
+        &["users", "create", &username, "--email", "not-an-email"],
+        "a brand new passphrase\n",
+    )
+    .await;
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not a valid email address"));
+}
+
+#[tokio::test]
+async fn a_user_can_be_given_an_email_for_password_resets() {
+    let app = spawn_app().await;
+    let email = format!("{}@example.com", Uuid::new_v4());
+    sqlx::query!("UPDATE users SET email = NULL WHERE username = $1", &app.test_user.username)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let output =
+        run_admin(&app, &["users", "set-email", &app.test_user.username, &email], "").await;
+
+    assert!(output.status.success(), "{:?}", output);
+    let saved =
+        sqlx::query!("SELECT email FROM users WHERE username = $1", &app.test_user.username)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(saved.email.as_deref(), Some(email.as_str()));
+}
+
+#[tokio::test]
+async fn setting_an_invalid_email_fails() {
+    let app = spawn_app().await;
+
+    let output =
+        run_admin(&app, &["users", "set-email", &app.test_user.username, "not-an-email"], "").await;
+
+    assert!(!output.status.success());
+    let saved =
+        sqlx::query!("SELECT email FROM users WHERE username = $1", &app.test_user.username)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(saved.email.as_deref(), Some(app.test_user.email.as_str()));
+}
+
+#[tokio::test]
+async fn a_disabled_user_is_logged_out_and_cannot_log_in_again_until_enabled() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+
+    let output = run_admin(&app, &["users", "disable", &app.test_user.username], "").await;
+    assert!(output.status.success(), "{:?}", output);
+
+    // The existing session is over...
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirected_to("/login", &response);
+    // ...and so are the API tokens...
+    let response = app.get_api_v1(&format!("/newsletters/{}", Uuid::new_v4()), &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // ...and logging in again fails.
+    let response = login(&app, &app.test_user.username, &app.test_user.password).await;
+    assert_is_redirected_to("/login", &response);
+
+    let output = run_admin(&app, &["users", "enable", &app.test_user.username], "").await;
+    assert!(output.status.success(), "{:?}", output);
+    let response = login(&app, &app.test_user.username, &app.test_user.password).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
+
+#[tokio::test]
+async fn disabling_an_unknown_user_fails() {
+    let app = spawn_app().await;
+
+    let output = run_admin(&app, &["users", "disable", "nobody"], "").await;
+
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn resetting_a_password_ends_sessions_and_replaces_the_old_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_password = "an entirely different passphrase";
+
+    let output = run_admin(
+        &app,
+        &["users", "reset-password", &app.test_user.username],
+        &format!("{}\n", new_password),
+    )
+    .await;
+
+    assert!(output.status.success(), "{:?}", output);
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirected_to("/login", &response);
+    let response = login(&app, &app.test_user.username, &app.test_user.password).await;
+    assert_is_redirected_to("/login", &response);
+    let response = login(&app, &app.test_user.username, new_password).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
+
+#[tokio::test]
+async fn requeued_issues_are_listed_in_the_queue() {
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO newsletter_issues (newsletter_issue_id, title, text_content, html_content, \
+         published_at) VALUES ($1, 'Title', 'Text', '<p>Html</p>', now()::TEXT)",
+    )
+    .bind(issue_id)
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    for (email, status) in
+        [("ursula@example.com", "confirmed"), ("pending@example.com", "pending_confirmation")]
+    {
+        sqlx::query(
+            "INSERT INTO subscriptions (id, email, name, subscribed_at, status) VALUES ($1, $2, \
+             'A name', now(), $3)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(email)
+        .bind(status)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let output = run_admin(&app, &["queue", "requeue", &issue_id.to_string()], "").await;
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Queued 1 deliveries"));
+    // Deliveries that are already queued are not duplicated.
+    let output = run_admin(&app, &["queue", "requeue", &issue_id.to_string()], "").await;
+    assert!(stdout(&output).contains("Queued 0 deliveries"));
+
+    let output = run_admin(&app, &["queue", "list"], "").await;
+    assert!(output.status.success(), "{:?}", output);
+    let listing = stdout(&output);
+    assert_eq!(listing.lines().count(), 1);
+    assert!(listing.starts_with(&format!("{}\tursula@example.com\t", issue_id)));
+}
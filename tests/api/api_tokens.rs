@@ -0,0 +1,187 @@
+use reqwest::header::WWW_AUTHENTICATE;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp};
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Release notes",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    })
+}
+
+// Publishes a newsletter issue with nothing but the API token to authenticate.
+async fn publish_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("{}/admin/newsletters", &app.address))
+        .bearer_auth(token)
+        .form(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_tokens() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response =
+        app.post_create_api_token(&[("name", "CI"), ("scope", "subscribers:read")]).await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn created_tokens_are_listed_without_their_secret() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Assert
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<td>CI</td><td>newsletters:publish</td>"));
+    assert!(!html_page.contains(&token));
+}
+
+#[tokio::test]
+async fn a_token_must_be_granted_at_least_one_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Create a token without scopes
+    let response = app.post_create_api_token(&[("name", "CI"), ("expires_in_days", "30")]).await;
+    assert_is_redirected_to("/admin/tokens", &response);
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>Please grant the token at least one scope.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_token_with_the_publish_scope_can_publish_a_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Act
+    let response = publish_with_token(&app, &token).await;
+
+    // Assert
+    assert_is_redirected_to("/admin/newsletters", &response);
+    let issues =
+        sqlx::query!("SELECT title FROM newsletter_issues").fetch_all(&app.db_pool).await.unwrap();
+    assert_eq!(issues.len(), 1);
+    let last_used_at = sqlx::query!("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .last_used_at;
+    assert!(last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn a_token_without_the_required_scope_is_forbidden() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:read"]).await;
+
+    // Act
+    let response = publish_with_token(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response.headers()[WWW_AUTHENTICATE],
+        r#"Bearer error="insufficient_scope", scope="newsletters:publish""#
+    );
+}
+
+#[tokio::test]
+async fn tokens_cannot_be_used_on_session_only_endpoints() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish", "subscribers:read"]).await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/password", &app.address))
+        .bearer_auth(&token)
+        .form(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "correct-stapler-orbit-violin",
+            "new_password_check": "correct-stapler-orbit-violin",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_unknown_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = publish_with_token(&app, "rrat_not-a-real-token").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()[WWW_AUTHENTICATE], r#"Bearer error="invalid_token""#);
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let token_id = sqlx::query!("SELECT token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .token_id;
+
+    // Act - Part 1 - Revoke the token
+    let response = app.post_revoke_api_token(&serde_json::json!({ "token_id": token_id })).await;
+    assert_is_redirected_to("/admin/tokens", &response);
+
+    // Act - Part 2 - Try to use it
+    let response = publish_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_expired_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    sqlx::query!("UPDATE api_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = publish_with_token(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
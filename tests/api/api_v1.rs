@@ -0,0 +1,241 @@
+use reqwest::header::WWW_AUTHENTICATE;
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+async fn assert_api_error(response: reqwest::Response, status: u16, code: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    let body: serde_json::Value = response.json().await.expect("The error body is not JSON.");
+    assert_eq!(body["error"]["code"], code);
+    assert!(body["error"]["message"].is_string());
+}
+
+// Subscribes through the API and returns the subscription token from the confirmation email.
+async fn create_unconfirmed_subscriber(app: &TestApp, email: &str) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_server)
+        .await;
+    let response = app
+        .post_api_v1(
+            "/subscriptions",
+            "",
+            &serde_json::json!({ "name": "le guin", "email": email }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let email_request = &app.mock_server.received_requests().await.unwrap().pop().unwrap();
+    let confirmation_link = app.get_confirmation_links(email_request).html;
+    confirmation_link
+        .query_pairs()
+        .find(|(k, _)| k == "subscription_token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+async fn create_confirmed_subscriber(app: &TestApp, email: &str) {
+    let subscription_token = create_unconfirmed_subscriber(app, email).await;
+    let response = app
+        .post_api_v1(
+            "/subscriptions/confirm",
+            "",
+            &serde_json::json!({ "subscription_token": subscription_token }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn create_api_token(app: &TestApp, scopes: &[&str]) -> String {
+    app.test_user.login(app).await;
+    app.create_api_token(scopes).await
+}
+
+#[tokio::test]
+async fn subscribing_returns_202_and_sends_a_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    create_unconfirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribing_with_invalid_data_returns_a_json_validation_error() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api_v1(
+            "/subscriptions",
+            "",
+            &serde_json::json!({ "name": "le guin", "email": "definitely-not-an-email" }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(response, 400, "validation_error").await;
+}
+
+#[tokio::test]
+async fn subscribing_too_often_returns_429() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscription_throttling.max_requests_per_ip = 1).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_server)
+        .await;
+    let body = |email: &str| serde_json::json!({ "name": "le guin", "email": email });
+    app.post_api_v1("/subscriptions", "", &body("first@example.com")).await;
+
+    // Act
+    let response = app.post_api_v1("/subscriptions", "", &body("second@example.com")).await;
+
+    // Assert
+    assert!(response.headers().contains_key("Retry-After"));
+    assert_api_error(response, 429, "too_many_requests").await;
+}
+
+#[tokio::test]
+async fn a_malformed_body_returns_a_json_error() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_api_v1("/subscriptions", "", &serde_json::json!({ "name": 42 })).await;
+
+    // Assert
+    assert_api_error(response, 400, "invalid_request").await;
+}
+
+#[tokio::test]
+async fn confirming_with_an_unknown_token_returns_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api_v1(
+            "/subscriptions/confirm",
+            "",
+            &serde_json::json!({ "subscription_token": "unknown" }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(response, 401, "invalid_subscription_token").await;
+}
+
+#[tokio::test]
+async fn protected_endpoints_reject_requests_without_a_valid_token() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_api_v1("/subscribers", "rrat_not-a-real-token").await;
+
+    // Assert
+    assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+    assert_api_error(response, 401, "authentication_failed").await;
+}
+
+#[tokio::test]
+async fn protected_endpoints_reject_tokens_without_the_required_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["newsletters:publish"]).await;
+
+    // Act
+    let response = app.get_api_v1("/subscribers", &token).await;
+
+    // Assert
+    assert_api_error(response, 403, "insufficient_scope").await;
+}
+
+#[tokio::test]
+async fn subscribers_can_be_listed_and_filtered_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app, "pending@example.com").await;
+    create_confirmed_subscriber(&app, "confirmed@example.com").await;
+    let token = create_api_token(&app, &["subscribers:read"]).await;
+
+    // Act
+    let all: serde_json::Value = app.get_api_v1("/subscribers", &token).await.json().await.unwrap();
+    let confirmed: serde_json::Value =
+        app.get_api_v1("/subscribers?status=confirmed", &token).await.json().await.unwrap();
+
+    // Assert
+    assert_eq!(all["subscribers"].as_array().unwrap().len(), 2);
+    let confirmed = confirmed["subscribers"].as_array().unwrap();
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0]["email"], "confirmed@example.com");
+    assert_eq!(confirmed[0]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn published_issues_report_their_delivery_status() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app, "confirmed@example.com").await;
+    let token = create_api_token(&app, &["newsletters:publish", "newsletters:read"]).await;
+    Mock::given(any()).respond_with(ResponseTemplate::new(200)).mount(&app.mock_server).await;
+
+    // Act - Part 1 - Publish
+    let response = app
+        .post_api_v1(
+            "/newsletters",
+            &token,
+            &serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let issue_id = response.json::<serde_json::Value>().await.unwrap()["issue_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    // Act - Part 2 - Delivery is pending
+    let status: serde_json::Value =
+        app.get_api_v1(&format!("/newsletters/{}", issue_id), &token).await.json().await.unwrap();
+    assert_eq!(status["title"], "Newsletter title");
+    assert_eq!(status["pending_deliveries"], 1);
+    assert_eq!(status["status"], "delivering");
+
+    // Act - Part 3 - Delivery is done
+    app.dispatch_all_pending_emails().await;
+    let status: serde_json::Value =
+        app.get_api_v1(&format!("/newsletters/{}", issue_id), &token).await.json().await.unwrap();
+    assert_eq!(status["pending_deliveries"], 0);
+    assert_eq!(status["status"], "delivered");
+}
+
+#[tokio::test]
+async fn querying_an_unknown_issue_returns_404() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["newsletters:read"]).await;
+
+    // Act
+    let response = app.get_api_v1(&format!("/newsletters/{}", Uuid::new_v4()), &token).await;
+
+    // Assert
+    assert_api_error(response, 404, "not_found").await;
+}
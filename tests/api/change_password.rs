@@ -129,3 +129,46 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirected_to("/admin/dashboard", &response);
 }
+
+#[tokio::test]
+async fn new_password_must_satisfy_the_password_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        ("short", "<p><i>The new password must be at least 12 characters long.</i></p>"),
+        (
+            app.test_user.password.as_str(),
+            "<p><i>The new password must be different from the current password.</i></p>",
+        ),
+        (
+            "password1234",
+            "<p><i>The new password is too common - please pick a less predictable one.</i></p>",
+        ),
+        (
+            "abcabcabcabcabc",
+            "<p><i>The new password is too easy to guess - try a longer passphrase made of \
+             unrelated words.</i></p>",
+        ),
+    ];
+
+    for (new_password, expected_message) in test_cases {
+        // Act
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": new_password,
+                "new_password_check": new_password,
+            }))
+            .await;
+
+        // Assert
+        assert_is_redirected_to("/admin/password", &response);
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(expected_message),
+            "The policy violation message was not shown when the new password was {}.",
+            new_password
+        );
+    }
+}
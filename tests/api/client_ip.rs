@@ -0,0 +1,42 @@
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+async fn login_forwarded_for(app: &TestApp, forwarded_for: &str) {
+    app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_ignored_unless_the_peer_is_a_trusted_proxy() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.trusted_proxies = vec![]).await;
+
+    // Act
+    login_forwarded_for(&app, &app.client_ip).await;
+
+    // Assert
+    let html_page = app.get_sessions_html().await;
+    assert!(!html_page.contains(&app.client_ip), "{}", html_page);
+    assert!(html_page.contains("127.0.0.1") || html_page.contains("::1"), "{}", html_page);
+}
+
+#[tokio::test]
+async fn addresses_the_client_puts_in_front_of_the_proxy_are_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    login_forwarded_for(&app, &format!("203.0.113.7, {}", &app.client_ip)).await;
+
+    // Assert
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains(&app.client_ip), "{}", html_page);
+    assert!(!html_page.contains("203.0.113.7"), "{}", html_page);
+}
@@ -0,0 +1,70 @@
+use std::process::Command;
+
+#[test]
+fn an_invalid_configuration_is_reported_in_full_at_startup() {
+    let output = Command::new(env!("CARGO_BIN_EXE_robust-rust"))
+        .env("APP_APPLICATION__HMAC_SECRET", "too-short")
+        .env("APP_EMAIL_CLIENT__SENDER_EMAIL", "not-an-email")
+        .output()
+        .expect("Failed to run the application binary.");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("application.hmac_secret: must be at least 64 bytes long"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("email_client.sender_email"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn an_unsupported_environment_is_reported_at_startup() {
+    let output = Command::new(env!("CARGO_BIN_EXE_robust-rust"))
+        .env("APP_ENVIRONMENT", "staging")
+        .output()
+        .expect("Failed to run the application binary.");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("staging is not a supported environment"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn secrets_can_be_read_from_files() {
+    let secret_file = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    std::fs::write(&secret_file, "too-short\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_robust-rust"))
+        .env("APP_APPLICATION__HMAC_SECRET_FILE", &secret_file)
+        .output()
+        .expect("Failed to run the application binary.");
+    std::fs::remove_file(&secret_file).unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The trailing newline is not part of the secret.
+    assert!(
+        stderr.contains("application.hmac_secret: must be at least 64 bytes long, not 9"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn a_missing_secret_file_is_reported_at_startup() {
+    let output = Command::new(env!("CARGO_BIN_EXE_robust-rust"))
+        .env("APP_DATABASE__PASSWORD_FILE", "/run/secrets/does-not-exist")
+        .output()
+        .expect("Failed to run the application binary.");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to read database.password from /run/secrets/does-not-exist"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,89 @@
+use robust_rust::configuration::{get_configuration, CookieSameSite, Settings};
+use robust_rust::startup::{Application, RunMode};
+use secrecy::Secret;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp};
+
+// Starts another instance of the application, sharing the database of `app`, and
+// returns its address.
+#[allow(clippy::let_underscore_future)]
+async fn spawn_instance(app: &TestApp, configure: impl FnOnce(&mut Settings)) -> String {
+    let mut configuration = get_configuration().unwrap();
+    configuration.database.database_name =
+        sqlx::query_scalar("SELECT current_database()").fetch_one(&app.db_pool).await.unwrap();
+    configuration.application.port = 0;
+    configuration.email_client.base_url = app.mock_server.uri();
+    configure(&mut configuration);
+    let application = Application::build(configuration, RunMode::All).await.unwrap();
+    let address = format!("http://localhost:{}", application.port());
+    let _ = tokio::spawn(application.run_until_stopped());
+    address
+}
+
+fn new_secret() -> Secret<String> {
+    Secret::new(format!("{}{}", Uuid::new_v4(), Uuid::new_v4()))
+}
+
+async fn get_admin_dashboard(app: &TestApp, address: &str) -> reqwest::Response {
+    app.api_client.get(format!("{}/admin/dashboard", address)).send().await.unwrap()
+}
+
+#[tokio::test]
+async fn sessions_survive_a_rotation_of_the_hmac_secret() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_secret = new_secret();
+
+    // The old secret is still accepted during the rotation...
+    let rotating = spawn_instance(&app, |c| {
+        c.application.previous_hmac_secrets = vec![c.application.hmac_secret.clone()];
+        c.application.hmac_secret = new_secret.clone();
+    })
+    .await;
+    let response = get_admin_dashboard(&app, &rotating).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|c| c.name() == "id"));
+
+    // ...and the session cookie it got in exchange works once it is retired.
+    let rotated = spawn_instance(&app, |c| c.application.hmac_secret = new_secret).await;
+    let response = get_admin_dashboard(&app, &rotated).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn sessions_signed_with_an_unknown_secret_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let address = spawn_instance(&app, |c| c.application.hmac_secret = new_secret()).await;
+    let response = get_admin_dashboard(&app, &address).await;
+
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn resigned_cookies_get_the_configured_attributes() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_secret = new_secret();
+
+    let rotating = spawn_instance(&app, |c| {
+        c.application.previous_hmac_secrets = vec![c.application.hmac_secret.clone()];
+        c.application.hmac_secret = new_secret;
+        c.cookies.same_site = CookieSameSite::Strict;
+    })
+    .await;
+    let response = get_admin_dashboard(&app, &rotating).await;
+
+    let cookie = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .find(|h| h.starts_with("id="))
+        .expect("The session cookie was not re-signed.");
+    assert!(cookie.contains("SameSite=Strict"), "{}", cookie);
+    assert!(cookie.contains("HttpOnly"), "{}", cookie);
+    assert!(cookie.contains("Path=/"), "{}", cookie);
+}
@@ -0,0 +1,152 @@
+use crate::helpers::{assert_is_redirected_to, csrf_token_in, spawn_app, TestApp};
+
+async fn post_logout_with_form(app: &TestApp, form: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_csrf_token_of_the_session() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let pages = [
+        app.get_admin_dashboard_html().await,
+        app.get_change_password_html().await,
+        app.get_publish_newsletter_html().await,
+        app.get_sessions_html().await,
+        app.get_api_tokens_html().await,
+    ];
+
+    // Assert
+    let token = csrf_token_in(&pages[0]).expect("The dashboard has no CSRF token.");
+    assert!(!token.is_empty());
+    for page in &pages {
+        let field = format!(r#"<input type="hidden" name="csrf_token" value="{}">"#, token);
+        assert_eq!(page.matches("<form").count(), page.matches(&field).count(), "{}", page);
+    }
+}
+
+#[tokio::test]
+async fn admin_posts_without_a_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.get_admin_dashboard().await;
+
+    // Act
+    let response = post_logout_with_form(&app, &[]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    // The session is still alive.
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn admin_posts_with_a_wrong_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.get_admin_dashboard().await;
+
+    // Act
+    let response = post_logout_with_form(&app, &[("csrf_token", "not-the-token")]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_csrf_token_of_another_session_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_session = app.new_api_client();
+    other_session
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .send()
+        .await
+        .unwrap();
+    let other_dashboard = other_session
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let other_token = csrf_token_in(&other_dashboard).unwrap();
+    app.get_admin_dashboard().await;
+
+    // Act
+    let response = post_logout_with_form(&app, &[("csrf_token", &other_token)]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_csrf_token_is_accepted_from_the_form() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = csrf_token_in(&app.get_admin_dashboard_html().await).unwrap();
+
+    // Act
+    let response = post_logout_with_form(&app, &[("csrf_token", &token)]).await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+    assert_is_redirected_to("/login", &app.get_admin_dashboard().await);
+}
+
+#[tokio::test]
+async fn anonymous_posts_are_still_sent_to_the_login_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = post_logout_with_form(&app, &[]).await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn the_session_cookie_is_secure_http_only_and_same_site() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // Assert
+    let cookie = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .find(|h| h.starts_with("id="))
+        .expect("No session cookie was set.");
+    assert!(cookie.contains("Secure"), "{}", cookie);
+    assert!(cookie.contains("HttpOnly"), "{}", cookie);
+    assert!(cookie.contains("SameSite=Lax"), "{}", cookie);
+    assert!(cookie.contains("Path=/"), "{}", cookie);
+}
@@ -1,6 +1,9 @@
 use reqwest::Client;
+use robust_rust::configuration::get_configuration;
+use robust_rust::issue_delivery_worker::record_heartbeat;
+use sqlx::{Connection, Executor, PgConnection};
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, TestApp};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -11,7 +14,7 @@ async fn health_check_works() {
     let client = Client::new();
     // Act
     let response = client
-        .get(&format!("{}/health_check", &app_details.address))
+        .get(format!("{}/health_check", &app_details.address))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -19,3 +22,117 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+async fn get_readiness(app: &TestApp) -> (u16, serde_json::Value) {
+    let response = Client::new()
+        .get(format!("{}/health/ready", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn readiness_fails_until_the_worker_has_reported_in() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (status, body) = get_readiness(&app).await;
+
+    // Assert
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["components"]["postgres"]["status"], "up");
+    assert_eq!(body["components"]["redis"]["status"], "up");
+    assert_eq!(body["components"]["issue_delivery_worker"]["status"], "down");
+    assert!(body["components"]["issue_delivery_worker"]["last_heartbeat"].is_null());
+}
+
+#[tokio::test]
+async fn readiness_succeeds_when_every_component_is_healthy() {
+    // Arrange
+    let app = spawn_app().await;
+    record_heartbeat(&app.db_pool).await.unwrap();
+
+    // Act
+    let (status, body) = get_readiness(&app).await;
+
+    // Assert
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["components"]["issue_delivery_worker"]["status"], "up");
+    assert!(body["components"]["issue_delivery_worker"]["last_heartbeat"].is_string());
+}
+
+#[tokio::test]
+async fn readiness_fails_when_the_worker_heartbeat_is_stale() {
+    // Arrange
+    let app = spawn_app().await;
+    record_heartbeat(&app.db_pool).await.unwrap();
+    sqlx::query!("UPDATE worker_heartbeats SET last_seen_at = now() - interval '5 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let (status, body) = get_readiness(&app).await;
+
+    // Assert
+    assert_eq!(status, 503);
+    assert_eq!(body["components"]["issue_delivery_worker"]["status"], "down");
+}
+
+#[tokio::test]
+async fn readiness_ignores_the_heartbeats_of_other_workers() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO worker_heartbeats (worker_name, last_seen_at) VALUES ('another-worker', \
+         now())"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let (status, body) = get_readiness(&app).await;
+
+    // Assert
+    assert_eq!(status, 503);
+    assert_eq!(body["components"]["issue_delivery_worker"]["status"], "down");
+}
+
+#[tokio::test]
+async fn readiness_fails_when_postgres_is_unreachable() {
+    // Arrange
+    let app = spawn_app().await;
+    record_heartbeat(&app.db_pool).await.unwrap();
+    let database_name: String =
+        sqlx::query_scalar("SELECT current_database()").fetch_one(&app.db_pool).await.unwrap();
+    // Keep new connections from coming in, then kick out the existing ones
+    let mut connection =
+        PgConnection::connect_with(&get_configuration().unwrap().database.without_db())
+            .await
+            .unwrap();
+    connection
+        .execute(
+            format!(r#"ALTER DATABASE "{}" WITH ALLOW_CONNECTIONS false"#, database_name).as_str(),
+        )
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE datname = $1")
+        .bind(&database_name)
+        .execute(&mut connection)
+        .await
+        .unwrap();
+
+    // Act
+    let (status, body) = get_readiness(&app).await;
+
+    // Assert
+    assert_eq!(status, 503);
+    assert_eq!(body["components"]["postgres"]["status"], "down");
+    assert_eq!(body["components"]["redis"]["status"], "up");
+}
@@ -1,11 +1,18 @@
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHasher};
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use robust_rust::configuration::{get_configuration, DatabaseSettings};
+use reqwest::cookie::{CookieStore, Jar};
+use robust_rust::configuration::{get_configuration, DatabaseSettings, Settings};
 use robust_rust::email_client::EmailClient;
 use robust_rust::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
-use robust_rust::startup::{get_connection_pool, Application};
+use robust_rust::migrations::MIGRATOR;
+use robust_rust::startup::{get_connection_pool, Application, RunMode};
 use robust_rust::telemetry::{get_subscriber, init_subscriber};
+use secrecy::ExposeSecret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
@@ -16,10 +23,12 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     let subscriber_name = "test".to_string();
 
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber =
+            get_subscriber(subscriber_name, default_filter_level, std::io::stdout, None).unwrap();
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber =
+            get_subscriber(subscriber_name, default_filter_level, std::io::sink, None).unwrap();
         init_subscriber(subscriber);
     };
 });
@@ -31,7 +40,12 @@ pub struct TestApp {
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    // The cookies of `api_client`.
+    pub cookie_jar: Arc<Jar>,
     pub email_client: EmailClient,
+    pub client_ip: String,
+    // What `/metrics` expects in an `Authorization: Bearer` header.
+    pub metrics_token: String,
 }
 
 pub struct ConfirmationLinks {
@@ -51,7 +65,7 @@ impl TestApp {
     }
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -82,7 +96,7 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -95,16 +109,68 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_forgot_password_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login/forgot", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_forgot_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/forgot", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_reset_password(&self, link: reqwest::Url) -> reqwest::Response {
+        self.api_client.get(link).send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/reset", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub fn get_password_reset_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let links: Vec<_> = linkify::LinkFinder::new()
+            .links(body["TextBody"].as_str().unwrap())
+            .filter(|l| *l.kind() == linkify::LinkKind::Url)
+            .collect();
+        assert_eq!(links.len(), 1);
+        let mut reset_link = reqwest::Url::parse(links[0].as_str()).unwrap();
+        assert_eq!(reset_link.host_str().unwrap(), "127.0.0.1");
+        reset_link.set_port(Some(self.port)).unwrap();
+        reset_link
+    }
+
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -114,9 +180,26 @@ impl TestApp {
         self.get_admin_dashboard().await.text().await.expect("Failed to get response text.")
     }
 
+    // The CSRF token of the session of `api_client`, as embedded in the dashboard.
+    // It is fetched by a client of its own, which leaves the flash message cookie
+    // in the cookie jar alone. Empty if there is no session.
+    pub async fn csrf_token(&self) -> String {
+        let mut request = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(format!("{}/admin/dashboard", &self.address));
+        let url = reqwest::Url::parse(&self.address).unwrap();
+        if let Some(cookies) = self.cookie_jar.cookies(&url) {
+            request = request.header(reqwest::header::COOKIE, cookies);
+        }
+        let html_page = request.send().await.unwrap().text().await.unwrap();
+        csrf_token_in(&html_page).unwrap_or_default()
+    }
+
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -131,7 +214,8 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
@@ -140,7 +224,125 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // A client with a cookie jar of its own, e.g. to log in from a second device.
+    pub fn new_api_client(&self) -> reqwest::Client {
+        build_api_client(&self.client_ip, Arc::new(Jar::default()))
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.get_sessions().await.text().await.expect("Failed to get response text.")
+    }
+
+    pub async fn post_revoke_session<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_all_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke_all", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to get response text.")
+    }
+
+    pub async fn post_create_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/tokens", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Creates an API token through the admin UI and returns it.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let mut body = vec![("name", "CI"), ("expires_in_days", "30")];
+        body.extend(scopes.iter().map(|s| ("scope", *s)));
+        let html_page = self.post_create_api_token(&body).await.text().await.unwrap();
+        html_page
+            .split(r#"<code id="api-token">"#)
+            .nth(1)
+            .and_then(|s| s.split("</code>").next())
+            .expect("The API token is not displayed.")
+            .to_owned()
+    }
+
+    pub async fn post_revoke_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/tokens/revoke", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_v1<Body>(
+        &self,
+        endpoint: &str,
+        token: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1{}", &self.address, endpoint))
+            .header("X-Forwarded-For", &self.client_ip)
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_v1(&self, endpoint: &str, token: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/api/v1{}", &self.address, endpoint))
+            .bearer_auth(token)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -148,7 +350,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -163,7 +365,8 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
@@ -175,6 +378,7 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
 }
 
 impl TestUser {
@@ -183,6 +387,7 @@ impl TestUser {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: "everythinghastostartsomewhere".into(),
+            email: format!("{}@example.com", Uuid::new_v4()),
         }
     }
 
@@ -204,12 +409,13 @@ impl TestUser {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (user_id, username, password_hash, email)
+            VALUES ($1, $2, $3, $4)
             "#,
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.email
         )
         .execute(pool)
         .await
@@ -217,8 +423,13 @@ impl TestUser {
     }
 }
 
-#[allow(clippy::let_underscore_future)]
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+// Spawns an app whose configuration has been tweaked by `configure`.
+#[allow(clippy::let_underscore_future)]
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let mock_server = MockServer::start().await;
@@ -228,6 +439,14 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0; // random free port
         c.email_client.base_url = mock_server.uri();
+        // Tests keep subscribing the same few addresses, while Redis is shared between
+        // them: the per-recipient limits are only turned on by the tests covering them.
+        c.subscription_throttling.max_requests_per_recipient = u64::MAX;
+        c.subscription_throttling.confirmation_email_cooldown_seconds = 0;
+        // Test clients pose as a proxy on the loopback interface, forwarding requests
+        // from an address of their own: see `client_ip` below.
+        c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        configure(&mut c);
         c
     };
 
@@ -236,8 +455,9 @@ pub async fn spawn_app() -> TestApp {
 
     // Launch our application as a background task
 
-    let application =
-        Application::build(configuration.clone()).await.expect("Failed to build app.");
+    let application = Application::build(configuration.clone(), RunMode::All)
+        .await
+        .expect("Failed to build app.");
 
     let application_port = application.port();
 
@@ -245,11 +465,11 @@ pub async fn spawn_app() -> TestApp {
 
     let _ = tokio::spawn(application.run_until_stopped());
 
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    // Every test app talks from its own address, so that failed logins recorded in
+    // the shared Redis instance by one test cannot lock out another.
+    let client_ip = std::net::Ipv6Addr::from(Uuid::new_v4().as_u128()).to_string();
+    let cookie_jar = Arc::new(Jar::default());
+    let client = build_api_client(&client_ip, cookie_jar.clone());
 
     let test_app = TestApp {
         address,
@@ -258,13 +478,37 @@ pub async fn spawn_app() -> TestApp {
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
+        cookie_jar,
         email_client: configuration.email_client.client(),
+        client_ip,
+        metrics_token: configuration.metrics.bearer_token.expose_secret().to_owned(),
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
+// The CSRF token embedded in the forms of `html_page`, if any.
+pub fn csrf_token_in(html_page: &str) -> Option<String> {
+    html_page
+        .split(r#"name="csrf_token" value=""#)
+        .nth(1)
+        .and_then(|s| s.split('"').next())
+        .map(str::to_owned)
+}
+
+fn build_api_client(client_ip: &str, cookie_jar: Arc<Jar>) -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_provider(cookie_jar)
+        .default_headers(reqwest::header::HeaderMap::from_iter([(
+            reqwest::header::HeaderName::from_static("x-forwarded-for"),
+            reqwest::header::HeaderValue::from_str(client_ip).unwrap(),
+        )]))
+        .build()
+        .unwrap()
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     // Create database
     let mut connection = PgConnection::connect_with(&config.without_db())
@@ -280,13 +524,41 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     let connection_pool =
         PgPool::connect_with(config.with_db()).await.expect("Failed to connect to Postgres.");
 
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
-        .await
-        .expect("Failed to migrate the database");
+    MIGRATOR.run(&connection_pool).await.expect("Failed to migrate the database");
     connection_pool
 }
 
+// A copy of the application binary running against `database_name`.
+// It is killed when the test is over, whether it passed or not.
+// When any worker last recorded a heartbeat. Workers record theirs under a name of
+// their own, so `last_heartbeat` does not see those of the processes started below.
+pub async fn latest_worker_heartbeat(pool: &PgPool) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT MAX(last_seen_at) FROM worker_heartbeats").fetch_one(pool).await
+}
+
+pub struct RunningProcess(pub Child);
+
+impl RunningProcess {
+    pub fn start(database_name: &str, args: &[&str], envs: &[(&str, &str)]) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_robust-rust"))
+            .args(args)
+            .env("APP_DATABASE__DATABASE_NAME", database_name)
+            .envs(envs.iter().copied())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to run the application binary.");
+        Self(child)
+    }
+}
+
+impl Drop for RunningProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
 pub fn assert_is_redirected_to(url: &str, response: &reqwest::Response) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("location").unwrap(), url);
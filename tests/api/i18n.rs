@@ -0,0 +1,158 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn post_subscriptions_in(
+    app: &TestApp,
+    body: &str,
+    accept_language: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", accept_language)
+        .body(body.to_owned())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn mock_email_server(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_server)
+        .await;
+}
+
+async fn sent_email(app: &TestApp) -> serde_json::Value {
+    let email_request = &app.mock_server.received_requests().await.unwrap()[0];
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+#[tokio::test]
+async fn the_confirmation_email_is_sent_in_the_language_of_the_browser() {
+    // Arrange
+    let app = spawn_app().await;
+    mock_email_server(&app).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    // Act
+    let response = post_subscriptions_in(&app, body, "fr-CH, fr;q=0.9, en;q=0.8").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email = sent_email(&app).await;
+    assert_eq!(email["Subject"], "Bienvenue !");
+    assert!(email["TextBody"].as_str().unwrap().contains("confirmer votre abonnement"));
+    let saved =
+        sqlx::query!("SELECT locale FROM subscriptions").fetch_one(&app.db_pool).await.unwrap();
+    assert_eq!(saved.locale, "fr");
+}
+
+#[tokio::test]
+async fn the_locale_picked_in_the_form_wins_over_the_browser() {
+    // Arrange
+    let app = spawn_app().await;
+    mock_email_server(&app).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=en";
+
+    // Act
+    post_subscriptions_in(&app, body, "fr").await;
+
+    // Assert
+    assert_eq!(sent_email(&app).await["Subject"], "Welcome!");
+    let saved =
+        sqlx::query!("SELECT locale FROM subscriptions").fetch_one(&app.db_pool).await.unwrap();
+    assert_eq!(saved.locale, "en");
+}
+
+#[tokio::test]
+async fn unsupported_languages_fall_back_to_english() {
+    // Arrange
+    let app = spawn_app().await;
+    mock_email_server(&app).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    // Act
+    post_subscriptions_in(&app, body, "de-DE, de;q=0.9").await;
+
+    // Assert
+    assert_eq!(sent_email(&app).await["Subject"], "Welcome!");
+}
+
+#[tokio::test]
+async fn validation_errors_are_reported_in_the_language_of_the_form() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response =
+        post_subscriptions_in(&app, "name=le%20guin&email=not-an-email&locale=fr", "en").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "« not-an-email » n'est pas une adresse e-mail valide."
+    );
+}
+
+#[tokio::test]
+async fn the_home_page_is_shown_in_the_language_of_the_browser() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response =
+        app.api_client.get(&app.address).header("Accept-Language", "fr").send().await.unwrap();
+
+    // Assert
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<html lang="fr">"#), "{}", html_page);
+    assert!(
+        html_page.contains("S&#x27;abonner") || html_page.contains("S&#39;abonner"),
+        "{}",
+        html_page
+    );
+    assert!(html_page.contains(r#"<input type="hidden" name="locale" value="fr">"#));
+}
+
+#[tokio::test]
+async fn the_confirmation_page_is_shown_in_the_language_of_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    mock_email_server(&app).await;
+    post_subscriptions_in(&app, "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=fr", "en")
+        .await;
+    let email_request = &app.mock_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = app.api_client.get(confirmation_link.html).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Votre abonnement est confirmé."), "{}", html_page);
+}
+
+#[tokio::test]
+async fn unknown_confirmation_tokens_are_reported_in_the_language_of_the_browser() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/subscriptions/confirm?subscription_token=unknown", &app.address))
+        .header("Accept-Language", "fr")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.text().await.unwrap(), "Ce lien de confirmation n'est pas valide.");
+}
@@ -0,0 +1,416 @@
+use std::time::Duration;
+
+use robust_rust::configuration::get_configuration;
+use robust_rust::idempotency::delete_expired_idempotency_keys;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+// Subscribes `email` from `client_ip`, passing `idempotency_key` as a header.
+async fn post_subscriptions_with_key(
+    app: &TestApp,
+    client_ip: &str,
+    idempotency_key: &str,
+    email: &str,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions", &app.address))
+        .header("X-Forwarded-For", client_ip)
+        .header("Idempotency-Key", idempotency_key)
+        .form(&serde_json::json!({ "name": "le guin", "email": email }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn publish_with_key(
+    app: &TestApp,
+    token: &str,
+    idempotency_key: &str,
+    title: &str,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/api/v1/newsletters", &app.address))
+        .bearer_auth(token)
+        .header("Idempotency-Key", idempotency_key)
+        .json(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn retried_requests_get_the_original_response() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Publish
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+    let first_body: serde_json::Value = response.json().await.unwrap();
+
+    // Act - Part 2 - Retry
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert_eq!(response.headers()["idempotent-replayed"], "true");
+    let second_body: serde_json::Value = response.json().await.unwrap();
+
+    // Assert
+    assert_eq!(first_body, second_body);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn anonymous_requests_are_deduplicated_per_client() {
+    // Arrange
+    let app = spawn_app().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_server)
+        .await;
+
+    // Act - Part 1 - The same client subscribes twice
+    for attempt in 0..2 {
+        let response = post_subscriptions_with_key(
+            &app,
+            &app.client_ip,
+            &idempotency_key,
+            "ursula_le_guin@gmail.com",
+        )
+        .await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers().contains_key("idempotent-replayed"), attempt > 0);
+    }
+
+    // Act - Part 2 - Another client happens to use the same key
+    let response =
+        post_subscriptions_with_key(&app, "203.0.113.7", &idempotency_key, "another@gmail.com")
+            .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+
+    // Mock verifies on Drop that we have sent exactly two confirmation emails
+}
+
+#[tokio::test]
+async fn failed_requests_can_be_retried_with_the_same_key() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    // Sabotage the database
+    sqlx::query!("ALTER TABLE issue_delivery_queue RENAME TO issue_delivery_queue_renamed")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act - Part 1 - Publishing fails
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 500);
+
+    // Act - Part 2 - Retry once the database is back
+    sqlx::query!("ALTER TABLE issue_delivery_queue_renamed RENAME TO issue_delivery_queue")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+}
+
+#[tokio::test]
+async fn an_issue_is_only_published_along_with_its_saved_response() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    // Sabotage the saving of responses, but not the claiming of keys
+    sqlx::query!("ALTER TABLE idempotency RENAME COLUMN response_body TO response_body_renamed")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act - Part 1 - Publishing fails once the issue has been stored
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 500);
+    let n_issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 0);
+
+    // Act - Part 2 - Retry once the database is back
+    sqlx::query!("ALTER TABLE idempotency RENAME COLUMN response_body_renamed TO response_body")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let n_issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn rate_limited_requests_can_be_retried_with_the_same_key() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscription_throttling.max_requests_per_ip = 1;
+        c.subscription_throttling.ip_window_seconds = 1;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = post_subscriptions_with_key(
+        &app,
+        &app.client_ip,
+        &Uuid::new_v4().to_string(),
+        "ursula_le_guin@gmail.com",
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 1 - Subscribing is rate limited
+    let response =
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com")
+            .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // Act - Part 2 - Retry once the window is over
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let response =
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com")
+            .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+}
+
+#[tokio::test]
+async fn reusing_a_key_for_a_different_request_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Act
+    let response = publish_with_key(&app, &token, &idempotency_key, "Another title").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+    // The original request can still be retried
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert_eq!(response.headers()["idempotent-replayed"], "true");
+}
+
+#[tokio::test]
+async fn the_same_body_on_another_endpoint_does_not_match_the_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_server)
+        .await;
+    let response =
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com")
+            .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/subscriptions", &app.address))
+        .header("X-Forwarded-For", &app.client_ip)
+        .header("Idempotency-Key", &idempotency_key)
+        .form(&serde_json::json!({ "name": "le guin", "email": "le_guin@gmail.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn concurrent_requests_with_the_same_key_get_the_same_response() {
+    // Arrange
+    let app = spawn_app().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Keep the first request in flight while the second one comes in
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+
+    // Act
+    let (response1, response2) = tokio::join!(
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com"),
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com"),
+    );
+
+    // Assert
+    assert_eq!(response1.status().as_u16(), 200);
+    assert_eq!(response2.status().as_u16(), 200);
+    let n_replayed = [&response1, &response2]
+        .iter()
+        .filter(|r| r.headers().contains_key("idempotent-replayed"))
+        .count();
+    assert_eq!(n_replayed, 1);
+    // Mock verifies on Drop that we have sent the confirmation email once
+}
+
+#[tokio::test]
+async fn a_request_that_waits_too_long_for_a_concurrent_one_is_told_to_retry() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.in_flight_timeout_ms = 100).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+
+    // Act - Part 1 - A second request comes in while the first one is in flight
+    let (response1, response2) = tokio::join!(
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com"),
+        async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com")
+                .await
+        },
+    );
+    assert_eq!(response1.status().as_u16(), 200);
+    assert_eq!(response2.status().as_u16(), 409);
+    assert_eq!(response2.headers()["retry-after"], "1");
+
+    // Act - Part 2 - Retry once the first request has completed
+    let response =
+        post_subscriptions_with_key(&app, &app.client_ip, &idempotency_key, "le_guin@gmail.com")
+            .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["idempotent-replayed"], "true");
+}
+
+// Makes every idempotency key of the app look as if it was first used `hours` ago.
+async fn age_idempotency_keys(app: &TestApp, hours: i32) {
+    sqlx::query!("UPDATE idempotency SET created_at = now() - make_interval(hours => $1)", hours)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn an_expired_key_is_treated_as_new() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.ttl_hours = 24).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = publish_with_key(&app, &token, &idempotency_key, "Newsletter title").await;
+    assert_eq!(response.status().as_u16(), 202);
+    age_idempotency_keys(&app, 25).await;
+
+    // Act - A different request, which would otherwise be rejected
+    let response = publish_with_key(&app, &token, &idempotency_key, "Another title").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 2);
+}
+
+#[tokio::test]
+async fn cleanup_purges_expired_keys_only() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_server)
+        .await;
+    for i in 0..3 {
+        let email = format!("le_guin_{}@gmail.com", i);
+        let key = Uuid::new_v4().to_string();
+        post_subscriptions_with_key(&app, &app.client_ip, &key, &email).await;
+    }
+    age_idempotency_keys(&app, 72).await;
+    post_subscriptions_with_key(&app, &app.client_ip, "fresh-key", "fresh@gmail.com").await;
+    let mut settings = get_configuration().unwrap().idempotency;
+    settings.ttl_hours = 48;
+    // Small batches, to go through more than one of them
+    settings.cleanup_batch_size = 2;
+
+    // Act
+    let n_purged = delete_expired_idempotency_keys(&app.db_pool, &settings).await.unwrap();
+
+    // Assert
+    assert_eq!(n_purged, 3);
+    let keys = sqlx::query!("SELECT idempotency_key FROM idempotency")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].idempotency_key, "fresh-key");
+}
+
+#[tokio::test]
+async fn an_invalid_idempotency_key_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response =
+        post_subscriptions_with_key(&app, &app.client_ip, &"a".repeat(60), "le_guin@gmail.com")
+            .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,11 +1,18 @@
+use std::time::Duration;
+
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use uuid::Uuid;
+
 use crate::helpers::{assert_is_redirected_to, spawn_app};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
     let app = spawn_app().await;
 
+    // A fresh username, so that failures recorded by earlier runs cannot lock it out.
     let login_body = serde_json::json!({
-        "username": "username",
+        "username": Uuid::new_v4().to_string(),
         "password": "password",
     });
 
@@ -36,3 +43,135 @@ async fn redirect_to_admin_dashboard_after_successful_login() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}!", &app.test_user.username)));
 }
+
+#[tokio::test]
+async fn repeated_failures_lock_the_username_out() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+    for _ in 0..5 {
+        let response = app.post_login(&wrong_login_body).await;
+        assert_is_redirected_to("/login", &response);
+    }
+
+    // Act - Even the right password is refused while locked out
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>Too many failed login attempts. Please try again in 15 minute(s).</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn repeated_failures_from_one_client_lock_that_client_out() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..20 {
+        let response = app
+            .post_login(&serde_json::json!({
+                "username": uuid::Uuid::new_v4().to_string(),
+                "password": "password",
+            }))
+            .await;
+        assert_is_redirected_to("/login", &response);
+    }
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts."));
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    let app = spawn_app().await;
+    let wrong_login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+
+    for _ in 0..4 {
+        app.post_login(&wrong_login_body).await;
+    }
+    let response = app.post_login(&login_body).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+
+    for _ in 0..4 {
+        app.post_login(&wrong_login_body).await;
+    }
+    let response = app.post_login(&login_body).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
+
+#[tokio::test]
+async fn a_weak_password_hash_is_upgraded_after_a_successful_login() {
+    // Arrange - Store a hash computed with the parameters of the seeded admin user
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_hash =
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(4096, 3, 1, None).unwrap())
+            .hash_password(app.test_user.password.as_bytes(), &salt)
+            .unwrap()
+            .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+
+    // Act
+    let response = app.post_login(&login_body).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+
+    // Assert - The hash is upgraded in the background
+    let mut upgraded_hash = weak_hash.clone();
+    for _ in 0..50 {
+        upgraded_hash = sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_id = $1",
+            app.test_user.user_id
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .password_hash;
+        if upgraded_hash != weak_hash {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(upgraded_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+
+    // Assert - The upgraded hash still matches the password
+    let response = app.post_login(&login_body).await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
@@ -1,8 +1,24 @@
+mod admin_cli;
 mod admin_dashboard;
+mod api_tokens;
+mod api_v1;
 mod change_password;
+mod client_ip;
+mod configuration;
+mod cookie_keys;
+mod csrf;
 mod health_check;
 mod helpers;
+mod i18n;
+mod idempotency;
 mod login;
+mod metrics;
 mod newsletter;
+mod openapi;
+mod password_reset;
+mod run_modes;
+mod security_headers;
+mod sessions;
+mod startup_migrations;
 mod subscriptions;
 mod subscriptions_confirm;
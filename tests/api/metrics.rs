@@ -0,0 +1,104 @@
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn get_metrics(app: &TestApp) -> reqwest::Response {
+    get_metrics_with_token(app, &app.metrics_token).await
+}
+
+async fn get_metrics_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("{}/metrics", &app.address))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// The registry is shared by every app spawned by the test suite: assertions can only
+// look for series, not for exact values.
+#[tokio::test]
+async fn metrics_are_exposed_in_the_prometheus_text_format() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = get_metrics(&app).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+    assert!(body.contains("issue_delivery_queue_depth "));
+    assert!(body.contains("issue_delivery_queue_oldest_task_age_seconds "));
+    assert!(body.contains(r#"db_pool_connections{state="max"} 10"#));
+}
+
+#[tokio::test]
+async fn metrics_cannot_be_scraped_without_the_configured_token() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let anonymous = reqwest::get(format!("{}/metrics", &app.address)).await.unwrap();
+    let wrong_token = get_metrics_with_token(&app, "not-the-metrics-token").await;
+
+    // Assert
+    for response in [anonymous, wrong_token] {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn requests_are_labelled_with_the_route_they_matched() {
+    // Arrange
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4();
+    app.get_api_v1(&format!("/newsletters/{}", issue_id), "not-a-token").await;
+
+    // Act
+    let body = get_metrics(&app).await.text().await.unwrap();
+
+    // Assert
+    assert!(body.contains(
+        r#"http_requests_total{method="GET",route="/api/v1/newsletters/{issue_id}",status="401"}"#
+    ));
+    assert!(body.contains(
+        r#"http_request_duration_seconds_count{method="GET",route="/api/v1/newsletters/{issue_id}"}"#
+    ));
+    assert!(!body.contains(&issue_id.to_string()));
+}
+
+#[tokio::test]
+async fn delivery_attempts_are_counted_by_outcome() {
+    // Arrange
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, text_content, html_content, published_at)
+        VALUES ($1, 'Title', 'Text', '<p>HTML</p>', now())
+        "#,
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email) VALUES ($1, \
+         'not-an-email')",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+    let body = get_metrics(&app).await.text().await.unwrap();
+
+    // Assert
+    assert!(body.contains(r#"issue_deliveries_total{outcome="invalid_email"}"#));
+}
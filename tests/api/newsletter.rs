@@ -176,6 +176,128 @@ async fn newsletter_creation_is_idempotent() {
     // Mock verifies on Drop that we have sent the newsletter email **once**
 }
 
+#[tokio::test]
+async fn a_key_sent_both_as_a_header_and_in_the_form_is_claimed_once() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let publish = || async {
+        app.api_client
+            .post(format!("{}/admin/newsletters", &app.address))
+            .header("X-CSRF-Token", app.csrf_token().await)
+            .header("Idempotency-Key", &idempotency_key)
+            .form(&serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+                "idempotency_key": idempotency_key,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    };
+
+    // Act - Part 1 - Publish the issue
+    let response = publish().await;
+    assert_is_redirected_to("/admin/newsletters", &response);
+
+    // Act - Part 2 - Retry
+    let response = publish().await;
+
+    // Assert
+    assert_is_redirected_to("/admin/newsletters", &response);
+    assert_eq!(response.headers()["Idempotent-Replayed"], "true");
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that the issue has been delivered once
+}
+
+#[tokio::test]
+async fn reusing_an_idempotency_key_for_a_different_issue_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Publish an issue
+    let response = app
+        .post_publish_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": idempotency_key,
+        }))
+        .await;
+    assert_is_redirected_to("/admin/newsletters", &response);
+
+    // Act - Part 2 - Publish a different issue with the same key
+    let response = app
+        .post_publish_newsletters(&serde_json::json!({
+            "title": "Another newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": idempotency_key,
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that only the first issue has been delivered
+}
+
+#[tokio::test]
+async fn queued_deliveries_carry_the_trace_context_of_the_publishing_request() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .header("traceparent", format!("00-{}-00f067aa0ba902b7-01", trace_id))
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirected_to("/admin/newsletters", &response);
+
+    // Assert
+    let task = sqlx::query!("SELECT trace_context FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let trace_context: serde_json::Value =
+        serde_json::from_str(&task.trace_context.unwrap()).unwrap();
+    let traceparent = trace_context["traceparent"].as_str().unwrap();
+    // Same trace, but the parent is now a span of ours
+    assert!(traceparent.starts_with(&format!("00-{}-", trace_id)));
+    assert!(!traceparent.contains("00f067aa0ba902b7"));
+}
+
 #[tokio::test]
 async fn concurrent_form_submission_is_handled_gracefully() {
     let app = spawn_app().await;
@@ -0,0 +1,131 @@
+use std::collections::BTreeSet;
+
+use crate::helpers::spawn_app;
+
+// The source of `startup::run`, where every route of the application is registered.
+const STARTUP_SOURCE: &str = include_str!("../../src/startup.rs");
+
+const HTTP_METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+// Extracts `(method, path)` pairs from `.route(...)` calls, resolving the prefixes of the
+// enclosing `web::scope(...)`s and the paths of `web::resource(...)`s.
+fn registered_routes(source: &str) -> BTreeSet<(String, String)> {
+    let mut routes = BTreeSet::new();
+    // `(path, depth)`: a scope or resource stays open while the nesting depth of
+    // parentheses does not drop below the depth at which it was declared.
+    let mut scopes: Vec<(String, usize)> = Vec::new();
+    let mut resource: Option<(String, usize)> = None;
+    let mut depth = 0;
+    let mut i = 0;
+    while i < source.len() {
+        let rest = &source[i..];
+        if let Some(path) = string_argument(rest, "web::scope(") {
+            scopes.push((path, depth));
+        } else if let Some(path) = string_argument(rest, "web::resource(") {
+            resource = Some((path, depth));
+        } else if let Some(path) = string_argument(rest, ".route(") {
+            let method = handler_method(&rest[rest.find(',').unwrap()..]);
+            routes.insert((method, prefix(&scopes) + &path));
+        } else if rest.starts_with(".route(web::") {
+            let method = handler_method(rest);
+            let path = &resource.as_ref().expect("A route outside of a resource").0;
+            routes.insert((method, prefix(&scopes) + path));
+        }
+        match rest.chars().next().unwrap() {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                scopes.retain(|(_, d)| *d <= depth);
+                resource = resource.filter(|(_, d)| *d <= depth);
+            }
+            _ => {}
+        }
+        i += rest.chars().next().unwrap().len_utf8();
+    }
+    routes
+}
+
+fn string_argument(source: &str, call: &str) -> Option<String> {
+    let rest = source.strip_prefix(call)?.strip_prefix('"')?;
+    Some(rest[..rest.find('"')?].to_owned())
+}
+
+fn handler_method(source: &str) -> String {
+    let rest = &source[source.find("web::").unwrap() + "web::".len()..];
+    rest[..rest.find("()").unwrap()].to_owned()
+}
+
+fn prefix(scopes: &[(String, usize)]) -> String {
+    scopes.iter().map(|(path, _)| path.as_str()).collect()
+}
+
+async fn get_openapi_document() -> serde_json::Value {
+    let app = spawn_app().await;
+    let response = reqwest::get(format!("{}/api/openapi.json", &app.address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("The OpenAPI document is not JSON.")
+}
+
+#[tokio::test]
+async fn the_openapi_document_describes_every_registered_route() {
+    // Arrange
+    let document = get_openapi_document().await;
+
+    // Act
+    let mut documented = BTreeSet::new();
+    for (path, item) in document["paths"].as_object().unwrap() {
+        for method in HTTP_METHODS.into_iter().filter(|m| item.get(*m).is_some()) {
+            documented.insert((method.to_owned(), path.to_owned()));
+        }
+    }
+
+    // Assert
+    let registered = registered_routes(STARTUP_SOURCE);
+    assert!(registered.len() > 20, "Failed to parse the routes out of `startup::run`.");
+    let undocumented: Vec<_> = registered.difference(&documented).collect();
+    let unregistered: Vec<_> = documented.difference(&registered).collect();
+    assert!(
+        undocumented.is_empty(),
+        "Routes missing from the OpenAPI document: {:?}",
+        undocumented
+    );
+    assert!(unregistered.is_empty(), "Documented routes that are not served: {:?}", unregistered);
+}
+
+#[tokio::test]
+async fn every_schema_referenced_by_the_openapi_document_is_defined() {
+    // Arrange
+    let document = get_openapi_document().await;
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+
+    // Act
+    let text = document.to_string();
+    let references: BTreeSet<&str> = text
+        .split(r##""$ref":"#/components/schemas/"##)
+        .skip(1)
+        .map(|s| &s[..s.find('"').unwrap()])
+        .collect();
+
+    // Assert
+    assert!(!references.is_empty());
+    for reference in references {
+        assert!(schemas.contains_key(reference), "{} is not defined", reference);
+    }
+}
+
+#[tokio::test]
+async fn the_api_explorer_loads_the_openapi_document() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/api/docs", &app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(r#"url: "/api/openapi.json""#));
+}
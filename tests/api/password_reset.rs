@@ -0,0 +1,234 @@
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirected_to, spawn_app, spawn_app_with, TestApp};
+
+async fn request_password_reset_link(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Send password reset email")
+        .expect(1)
+        .mount_as_scoped(&app.mock_server)
+        .await;
+
+    let response =
+        app.post_forgot_password(&serde_json::json!({ "email": &app.test_user.email })).await;
+    assert_is_redirected_to("/login", &response);
+
+    let email_request = &app.mock_server.received_requests().await.unwrap().pop().unwrap();
+    app.get_password_reset_link(email_request)
+}
+
+fn token_from(link: &reqwest::Url) -> String {
+    link.query_pairs().find(|(k, _)| k == "token").unwrap().1.into_owned()
+}
+
+#[tokio::test]
+async fn forgot_password_does_not_reveal_whether_an_email_is_registered() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_server)
+        .await;
+
+    // Act
+    let response =
+        app.post_forgot_password(&serde_json::json!({ "email": "nobody@example.com" })).await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If an account is registered with that email, a link to reset your password has \
+         been sent to it.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn forgot_password_rejects_an_invalid_email() {
+    let app = spawn_app().await;
+
+    let response =
+        app.post_forgot_password(&serde_json::json!({ "email": "definitely-not-an-email" })).await;
+
+    assert_is_redirected_to("/login/forgot", &response);
+    let html_page = app.get_forgot_password_html().await;
+    assert!(html_page.contains("is not a valid email address."));
+}
+
+#[tokio::test]
+async fn the_reset_link_lets_the_user_choose_a_new_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Request a reset link
+    let reset_link = request_password_reset_link(&app).await;
+    let response = app.get_reset_password(reset_link.clone()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Choose a new password
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token_from(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirected_to("/login", &response);
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset. You can now log in.</i></p>"));
+
+    // Act - Part 3 - The old password no longer works, the new one does
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirected_to("/login", &response);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password
+        }))
+        .await;
+    assert_is_redirected_to("/admin/dashboard", &response);
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let reset_link = request_password_reset_link(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+    let body = serde_json::json!({
+        "token": token_from(&reset_link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+
+    let response = app.post_reset_password(&body).await;
+    assert_is_redirected_to("/login", &response);
+
+    let response = app.post_reset_password(&body).await;
+    assert_is_redirected_to("/login/forgot", &response);
+    let html_page = app.get_forgot_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>The password reset link is invalid or has expired. Please request a new \
+         one.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    let app = spawn_app().await;
+    let reset_link = request_password_reset_link(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token_from(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirected_to("/login/forgot", &response);
+}
+
+#[tokio::test]
+async fn reset_tokens_are_not_stored_in_plain_text() {
+    let app = spawn_app().await;
+    let reset_link = request_password_reset_link(&app).await;
+
+    let saved = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_ne!(saved.token_hash, token_from(&reset_link));
+}
+
+#[tokio::test]
+async fn resetting_the_password_logs_out_existing_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let reset_link = request_password_reset_link(&app).await;
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token_from(&reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirected_to("/login", &response);
+
+    // Assert
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn the_reset_token_is_escaped_in_the_form() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut link = reqwest::Url::parse(&format!("{}/login/reset", &app.address)).unwrap();
+    link.query_pairs_mut().append_pair("token", r#""><script>alert(1)</script>"#);
+
+    // Act
+    let html_page = app.get_reset_password(link).await.text().await.unwrap();
+
+    // Assert
+    assert!(html_page.contains(r#"value="&quot;&gt;&lt;script&gt;"#), "{}", html_page);
+    assert!(!html_page.contains("<script>"));
+}
+
+#[tokio::test]
+async fn a_new_password_built_from_the_email_of_the_user_is_rejected() {
+    // Arrange
+    let app = spawn_app_with(|c| c.password_policy.min_strength_score = Some(3)).await;
+    let reset_link = request_password_reset_link(&app).await;
+    let token = token_from(&reset_link);
+
+    // Act - Part 1 - Try a password the user's email gives away
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": &app.test_user.email,
+            "new_password_check": &app.test_user.email,
+        }))
+        .await;
+
+    // Assert - Part 1
+    assert_is_redirected_to(&format!("/login/reset?token={}", token), &response);
+    let reset_form = format!("{}/login/reset?token={}", &app.address, token);
+    let html_page = app.get_reset_password(reset_form.parse().unwrap()).await.text().await.unwrap();
+    assert!(html_page.contains("The new password is too easy to guess"), "{}", html_page);
+
+    // Act - Part 2 - The link still works for a better password
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert - Part 2
+    assert_is_redirected_to("/login", &response);
+}
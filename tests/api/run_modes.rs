@@ -0,0 +1,82 @@
+use std::net::TcpListener;
+use std::time::Duration;
+
+use crate::helpers::{latest_worker_heartbeat, spawn_app, RunningProcess, TestApp};
+
+// Runs the main binary in `mode` against the database of `app`, serving on `port`.
+async fn run_in_mode(app: &TestApp, mode: &str, port: u16) -> RunningProcess {
+    let database_name: String =
+        sqlx::query_scalar("SELECT current_database()").fetch_one(&app.db_pool).await.unwrap();
+    RunningProcess::start(&database_name, &[mode], &[("APP_APPLICATION__PORT", &port.to_string())])
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+async fn get(port: u16, path: &str) -> Result<reqwest::Response, reqwest::Error> {
+    reqwest::get(format!("http://127.0.0.1:{}{}", port, path)).await
+}
+
+// Waits for the process to answer its health check.
+async fn wait_until_serving(port: u16) {
+    for _ in 0..50 {
+        if get(port, "/health_check").await.is_ok_and(|r| r.status().is_success()) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The process never started serving HTTP.");
+}
+
+async fn get_readiness(port: u16) -> (u16, serde_json::Value) {
+    let response = get(port, "/health/ready").await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn worker_mode_runs_the_worker_and_only_serves_health_and_metrics() {
+    let app = spawn_app().await;
+    let port = free_port();
+
+    let _process = run_in_mode(&app, "worker", port).await;
+
+    let mut heartbeat = None;
+    for _ in 0..50 {
+        heartbeat = latest_worker_heartbeat(&app.db_pool).await.unwrap();
+        if heartbeat.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(heartbeat.is_some(), "The worker never recorded a heartbeat.");
+    wait_until_serving(port).await;
+    let (status, body) = get_readiness(port).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["components"]["issue_delivery_worker"]["status"], "up");
+    assert!(body["components"].get("redis").is_none());
+    let metrics = reqwest::Client::new()
+        .get(format!("http://127.0.0.1:{}/metrics", port))
+        .bearer_auth(&app.metrics_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(metrics.status().as_u16(), 200);
+    assert_eq!(get(port, "/login").await.unwrap().status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn api_mode_serves_http_without_running_the_worker() {
+    let app = spawn_app().await;
+    let port = free_port();
+
+    let _process = run_in_mode(&app, "api", port).await;
+
+    wait_until_serving(port).await;
+    assert!(latest_worker_heartbeat(&app.db_pool).await.unwrap().is_none());
+    // Readiness does not depend on a worker that runs elsewhere.
+    let (status, body) = get_readiness(port).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["components"]["redis"]["status"], "up");
+    assert!(body["components"].get("issue_delivery_worker").is_none());
+}
@@ -0,0 +1,145 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+
+fn header<'a>(response: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    response.headers().get(name).map(|h| h.to_str().unwrap())
+}
+
+// The nonce the policy allows inline elements with.
+fn nonce_in(policy: &str) -> &str {
+    policy
+        .split("'nonce-")
+        .nth(1)
+        .and_then(|s| s.split('\'').next())
+        .expect("The policy has no nonce.")
+}
+
+#[tokio::test]
+async fn pages_are_served_with_security_headers() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.security_headers.csp_report_only = false;
+        c.security_headers.hsts_max_age_seconds = 31536000;
+    })
+    .await;
+
+    // Act
+    let response = app.api_client.get(&app.address).send().await.unwrap();
+
+    // Assert
+    let policy = header(&response, "Content-Security-Policy").expect("No CSP was sent.");
+    assert!(policy.contains("frame-ancestors 'none'"), "{}", policy);
+    assert!(policy.ends_with("report-uri /csp-report"), "{}", policy);
+    assert!(header(&response, "Content-Security-Policy-Report-Only").is_none());
+    assert_eq!(header(&response, "X-Frame-Options"), Some("DENY"));
+    assert_eq!(header(&response, "Referrer-Policy"), Some("same-origin"));
+    assert_eq!(header(&response, "X-Content-Type-Options"), Some("nosniff"));
+    assert_eq!(
+        header(&response, "Strict-Transport-Security"),
+        Some("max-age=31536000; includeSubDomains")
+    );
+}
+
+#[tokio::test]
+async fn inline_styles_carry_the_nonce_of_the_request() {
+    // Arrange
+    let app = spawn_app_with(|c| c.security_headers.csp_report_only = false).await;
+
+    // Act
+    let first = app.api_client.get(&app.address).send().await.unwrap();
+    let second = app.api_client.get(&app.address).send().await.unwrap();
+
+    // Assert
+    let first_nonce = nonce_in(header(&first, "Content-Security-Policy").unwrap()).to_owned();
+    let second_nonce = nonce_in(header(&second, "Content-Security-Policy").unwrap()).to_owned();
+    assert_ne!(first_nonce, second_nonce);
+    let html_page = first.text().await.unwrap();
+    assert!(html_page.contains(&format!(r#"<style nonce="{}">"#, first_nonce)), "{}", html_page);
+}
+
+#[tokio::test]
+async fn the_policy_is_only_reported_in_report_only_mode() {
+    // Arrange
+    let app = spawn_app_with(|c| c.security_headers.csp_report_only = true).await;
+
+    // Act
+    let response = app.api_client.get(format!("{}/login", &app.address)).send().await.unwrap();
+
+    // Assert
+    assert!(header(&response, "Content-Security-Policy").is_none());
+    let policy = header(&response, "Content-Security-Policy-Report-Only").unwrap();
+    assert!(policy.ends_with("report-uri /csp-report"), "{}", policy);
+}
+
+#[tokio::test]
+async fn hsts_is_left_out_when_disabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.security_headers.hsts_max_age_seconds = 0).await;
+
+    // Act
+    let response = app.api_client.get(&app.address).send().await.unwrap();
+
+    // Assert
+    assert!(header(&response, "Strict-Transport-Security").is_none());
+    assert_eq!(header(&response, "X-Frame-Options"), Some("DENY"));
+}
+
+#[tokio::test]
+async fn the_api_explorer_keeps_its_own_policy() {
+    // Arrange
+    let app = spawn_app_with(|c| c.security_headers.csp_report_only = false).await;
+
+    // Act
+    let response = app.api_client.get(format!("{}/api/docs", &app.address)).send().await.unwrap();
+
+    // Assert
+    let policy = header(&response, "Content-Security-Policy").unwrap();
+    assert!(policy.contains("https://unpkg.com"), "{}", policy);
+    let nonce = nonce_in(policy).to_owned();
+    let html_page = response.text().await.unwrap();
+    assert_eq!(html_page.matches(&format!(r#"<script nonce="{}""#, nonce)).count(), 2);
+}
+
+#[tokio::test]
+async fn csp_violation_reports_are_accepted() {
+    // Arrange
+    let app = spawn_app().await;
+    let report = serde_json::json!({
+        "csp-report": {
+            "document-uri": "http://127.0.0.1/login",
+            "violated-directive": "script-src",
+            "blocked-uri": "inline"
+        }
+    });
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/csp-report", &app.address))
+        .header("Content-Type", "application/csp-report")
+        .body(report.to_string())
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 204);
+}
+
+#[tokio::test]
+async fn oversized_csp_reports_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/csp-report", &app.address))
+        .header("Content-Type", "application/csp-report")
+        .body("x".repeat(64 * 1024))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 413);
+}
@@ -0,0 +1,156 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp};
+
+// Logs the test user in with a client of its own, standing in for another device.
+async fn log_in_from_another_device(app: &TestApp, user_agent: &str) -> reqwest::Client {
+    let client = app.new_api_client();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .header(reqwest::header::USER_AGENT, user_agent)
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirected_to("/admin/dashboard", &response);
+    client
+}
+
+async fn get_admin_dashboard_with(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// Extracts the ids that can be revoked from the sessions page.
+fn revocable_session_ids(html_page: &str) -> Vec<String> {
+    html_page
+        .split(r#"name="session_id" value=""#)
+        .skip(1)
+        .map(|s| s.split('"').next().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_sessions().await;
+
+    // Assert
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn every_session_of_the_user_is_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    log_in_from_another_device(&app, "<b>Other device</b>").await;
+
+    // Act
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert!(html_page.contains("This session"));
+    assert!(html_page.contains("&lt;b&gt;Other device&lt;/b&gt;"));
+    assert!(html_page.contains(&app.client_ip));
+    assert_eq!(revocable_session_ids(&html_page).len(), 1);
+}
+
+#[tokio::test]
+async fn revoking_a_session_logs_the_other_device_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_from_another_device(&app, "Other device").await;
+    let session_ids = revocable_session_ids(&app.get_sessions_html().await);
+
+    // Act - Part 1 - Revoke the other session
+    let response =
+        app.post_revoke_session(&serde_json::json!({ "session_id": &session_ids[0] })).await;
+    assert_is_redirected_to("/admin/sessions", &response);
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>The session has been revoked.</i></p>"));
+    assert!(!html_page.contains("Other device"));
+
+    // Act - Part 3 - The other device is logged out, we are not
+    let response = get_admin_dashboard_with(&app, &other_device).await;
+    assert_is_redirected_to("/login", &response);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn revoking_an_unknown_session_does_not_log_anybody_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_from_another_device(&app, "Other device").await;
+
+    // Act
+    let response = app
+        .post_revoke_session(&serde_json::json!({ "session_id": Uuid::new_v4().to_string() }))
+        .await;
+
+    // Assert
+    assert_is_redirected_to("/admin/sessions", &response);
+    let response = get_admin_dashboard_with(&app, &other_device).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_everywhere_logs_every_device_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_from_another_device(&app, "Other device").await;
+
+    // Act - Part 1 - Log out everywhere
+    let response = app.post_revoke_all_sessions().await;
+    assert_is_redirected_to("/login", &response);
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>You have been logged out of every session.</i></p>"));
+
+    // Act - Part 3 - Every device is logged out
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirected_to("/login", &response);
+    let response = get_admin_dashboard_with(&app, &other_device).await;
+    assert_is_redirected_to("/login", &response);
+}
+
+#[tokio::test]
+async fn changing_password_logs_out_every_other_session() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_from_another_device(&app, "Other device").await;
+    let new_password = "correct-stapler-orbit-violin";
+
+    // Act
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+    assert_is_redirected_to("/admin/password", &response);
+
+    // Assert
+    let response = get_admin_dashboard_with(&app, &other_device).await;
+    assert_is_redirected_to("/login", &response);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
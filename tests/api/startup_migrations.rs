@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use robust_rust::configuration::get_configuration;
+use sqlx::{Connection, Executor, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::helpers::{latest_worker_heartbeat, spawn_app, RunningProcess};
+
+// Creates a database without any table in it.
+async fn create_empty_database() -> (String, PgPool) {
+    let mut settings = get_configuration().unwrap().database;
+    settings.database_name = Uuid::new_v4().to_string();
+    let mut connection = PgConnection::connect_with(&settings.without_db()).await.unwrap();
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, settings.database_name).as_str())
+        .await
+        .unwrap();
+    let pool = PgPool::connect_with(settings.with_db()).await.unwrap();
+    (settings.database_name, pool)
+}
+
+#[tokio::test]
+async fn migrations_are_applied_on_startup_when_enabled() {
+    let (database_name, pool) = create_empty_database().await;
+    // Workers serve their health checks over HTTP: each gets a port of its own.
+    let migrate = [("APP_DATABASE__MIGRATE_ON_STARTUP", "true"), ("APP_APPLICATION__PORT", "0")];
+
+    // Two instances starting together must not trip over each other.
+    let mut first = RunningProcess::start(&database_name, &["worker"], &migrate);
+    let mut second = RunningProcess::start(&database_name, &["worker"], &migrate);
+
+    let mut heartbeat = None;
+    for _ in 0..100 {
+        // Fails until the migrations have created the table.
+        heartbeat = latest_worker_heartbeat(&pool).await.ok().flatten();
+        if heartbeat.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(heartbeat.is_some(), "The worker never came up.");
+    assert!(first.0.try_wait().unwrap().is_none());
+    assert!(second.0.try_wait().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn startup_is_refused_when_the_schema_is_newer_than_the_binary() {
+    let app = spawn_app().await;
+    let database_name: String =
+        sqlx::query_scalar("SELECT current_database()").fetch_one(&app.db_pool).await.unwrap();
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (99991231000000, 'from the future', true, '\\x00', 0)",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let mut process = RunningProcess::start(
+        &database_name,
+        &["worker"],
+        &[("APP_DATABASE__MIGRATE_ON_STARTUP", "true")],
+    );
+
+    let mut status = None;
+    for _ in 0..100 {
+        status = process.0.try_wait().unwrap();
+        if status.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(status.is_some_and(|s| !s.success()), "The worker started anyway.");
+    assert!(latest_worker_heartbeat(&app.db_pool).await.unwrap().is_none());
+}
@@ -1,7 +1,9 @@
+use robust_rust::configuration::Settings;
+use uuid::Uuid;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -141,3 +143,124 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+fn subscription_body(email: &str) -> String {
+    serde_urlencoded::to_string([("name", "le guin"), ("email", email)]).unwrap()
+}
+
+fn unique_email() -> String {
+    format!("{}@example.com", Uuid::new_v4())
+}
+
+#[tokio::test]
+async fn subscribe_ignores_requests_that_fill_in_the_honeypot() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_server)
+        .await;
+
+    let body =
+        format!("{}&website=http%3A%2F%2Fspam.example.com", subscription_body(&unique_email()));
+    let response = app.post_subscriptions(body).await;
+
+    // The bot cannot tell that it has been caught.
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscriptions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscriptions, 0);
+}
+
+#[tokio::test]
+async fn subscribe_rate_limits_requests_per_client_ip() {
+    let app = spawn_app_with(|c| c.subscription_throttling.max_requests_per_ip = 2).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app.post_subscriptions(subscription_body(&unique_email())).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let response = app.post_subscriptions(subscription_body(&unique_email())).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"].to_str().unwrap().parse().unwrap();
+    assert!((1..=3600).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn subscribe_rate_limits_requests_per_recipient_across_clients() {
+    let configure = |c: &mut Settings| c.subscription_throttling.max_requests_per_recipient = 1;
+    let app = spawn_app_with(configure).await;
+    let other_app = spawn_app_with(configure).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_server)
+        .await;
+    let email = unique_email();
+
+    let response = app.post_subscriptions(subscription_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    // Another client, asking for the same recipient.
+    let response = other_app.post_subscriptions(subscription_body(&email)).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn confirmation_emails_to_the_same_address_are_subject_to_a_cooldown() {
+    let configure =
+        |c: &mut Settings| c.subscription_throttling.confirmation_email_cooldown_seconds = 300;
+    let app = spawn_app_with(configure).await;
+    let other_app = spawn_app_with(configure).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&other_app.mock_server)
+        .await;
+    let email = unique_email();
+
+    app.post_subscriptions(subscription_body(&email)).await.error_for_status().unwrap();
+    let response = other_app.post_subscriptions(subscription_body(&email)).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"].to_str().unwrap().parse().unwrap();
+    assert!((1..=300).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn a_confirmation_email_that_could_not_be_sent_does_not_start_the_cooldown() {
+    let app =
+        spawn_app_with(|c| c.subscription_throttling.confirmation_email_cooldown_seconds = 300)
+            .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_server)
+        .await;
+    let email = unique_email();
+
+    let response = app.post_subscriptions(subscription_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 500);
+    let response = app.post_subscriptions(subscription_body(&email)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -7,7 +7,7 @@ use crate::helpers::spawn_app;
 async fn confirmations_without_token_are_rejected_with_400() {
     let app = spawn_app().await;
 
-    let reponse = reqwest::get(&format!("{}/subscriptions/confirm", &app.address))
+    let reponse = reqwest::get(format!("{}/subscriptions/confirm", &app.address))
         .await
         .expect("Failed to execute request.");
 